@@ -5,12 +5,14 @@
 pub const TREASURY_INITIAL_AMOUNT: u64 = 300_000_000;
 
 ////////////////////////////////////////////////////////////
-///                 Six Month Cliff Constants            ///
+///                 Treasury Vesting Constants           ///
 ////////////////////////////////////////////////////////////
 
-pub const SIX_MONTHS: i64 = 15768000; // 6 months in unix timestamp
-pub const SIX_MONTHS_TRANSFERS_PER_PERIOD: u64 = 2;
-pub const SIX_MONTH_CLIFF_AMOUNT: u64 = 50_000_000;
+// Vesting schedule of the authority created with the treasury, it replaces the six month cliff
+pub const TREASURY_VESTING_SCHEDULE_ID: u64 = 0;
+pub const TREASURY_VESTING_PERIOD: i64 = 15768000; // 6 months in unix timestamp
+pub const TREASURY_VESTING_PERIODS: u64 = 3;
+pub const TREASURY_VESTING_PERIOD_AMOUNT: u64 = 50_000_000;
 
 ////////////////////////////////////////////////////////////
 ///                 Lottery Constants                    ///
@@ -22,6 +24,27 @@ pub const COMBINATION_LENGTH: usize = 8;
 pub const PAY_LOTTERY_TYPE: u8 = 0;
 pub const LOCK_LOTTERY_TYPE: u8 = 1;
 pub const LOTTERY_CLOSE_TIME_BUFFER: i64 = 10*24*60*60; // 10 days
+pub const TICKET_NFT_NAME: &str = "DRACO Lottery Ticket";
+pub const TICKET_NFT_SYMBOL: &str = "DRACOT";
+pub const TICKET_NFT_URI: &str = "";
+pub const MAX_PAYOUT_BOUNTY: u64 = 100; // DRACO paid from the treasury per cranked payout at most
+pub const PAYOUT_BOUNTY_GRACE_PERIOD: i64 = 3*24*60*60; // 3 days for owners to claim before cranking earns a bounty
+
+////////////////////////////////////////////////////////////
+///                 Marketplace Constants                ///
+////////////////////////////////////////////////////////////
+
+pub const TICKET_SALE_FEE_BPS: u64 = 250; // 2.5% of the price goes to the treasury
+
+////////////////////////////////////////////////////////////
+///                 Airdrop Constants                    ///
+////////////////////////////////////////////////////////////
+
+pub const AIRDROP_CLAIM_BITMAP_WORDS: usize = 128;
+pub const AIRDROP_CLAIM_BITMAP_BITS: u64 = AIRDROP_CLAIM_BITMAP_WORDS as u64 * 64;
+pub const AIRDROP_MAX_BALANCE_TIERS: usize = 8;
+// Prefix of the message signed for Signed mode claims, followed by the program id
+pub const AIRDROP_CLAIM_MESSAGE_TAG: &[u8] = b"draco_protocol:claim_airdrop";
 
 ////////////////////////////////////////////////////////////
 ///         Lottery Reward Factors  (Initial values)     ///
@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct AirdropExtended {
+    pub airdrop_id: u64,
+    pub previous_end_datetime: i64,
+    pub end_datetime: i64,
+}
+
+#[event]
+pub struct AirdropSupplyIncreased {
+    pub airdrop_id: u64,
+    pub added_supply: u64,
+    pub supply: u64,
+}
+
+#[event]
+pub struct AirdropPauseUpdated {
+    pub airdrop_id: u64,
+    pub is_paused: bool,
+}
+
+#[event]
+pub struct AirdropCancelled {
+    pub airdrop_id: u64,
+    pub supplied: u64,
+    // Whole tokens never claimed, returned to the treasury
+    pub unused_amount: u64,
+}
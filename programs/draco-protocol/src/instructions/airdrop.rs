@@ -1,11 +1,22 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::{ed25519_program, sysvar::instructions as instructions_sysvar};
 
-pub use crate::state::airdrop::Airdrop;
+pub use crate::state::airdrop::{Airdrop, AirdropBalanceTier, AirdropEligibility, AirdropMode, AirdropVesting};
 pub use crate::state::airdrop_claimed::AirdropClaimed;
+pub use crate::state::airdrop_claim_bitmap::AirdropClaimBitmap;
+pub use crate::state::airdrop_vesting_position::AirdropVestingPosition;
+pub use crate::state::airdrop_registration::AirdropRegistration;
+pub use crate::state::airdrop_tombstone::AirdropTombstone;
+use crate::constants::{AIRDROP_CLAIM_BITMAP_BITS, AIRDROP_CLAIM_MESSAGE_TAG, AIRDROP_MAX_BALANCE_TIERS};
 use crate::{instructions, state::protocol_authority::ProtocolAuthority};
 use crate::state::protocol_error::ErrorCode;
+use crate::events::{AirdropCancelled, AirdropExtended, AirdropPauseUpdated, AirdropSupplyIncreased};
 use anchor_spl::{associated_token::AssociatedToken, token_interface::{ Mint, TokenInterface, TokenAccount}};
-use crate::instructions::pda_owned_token_accounts;
+use crate::instructions::{lottery, pda_owned_token_accounts};
+use crate::state::lottery::LotteryState;
+use crate::state::participant::Participant;
+use crate::state::ticket::Ticket;
 
 #[derive(Accounts)]
 #[instruction(airdrop_id: u64)]
@@ -25,39 +36,542 @@ pub struct CreateAirdrop<'info> {
     )]
     pub airdrop: Account<'info, Airdrop>,
 
+    /// CHECK: Must be empty, it only exists if an airdrop with this id was already closed.
+    #[account(
+        seeds = [
+            b"airdrop_tombstone".as_ref(),
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub airdrop_tombstone: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    // Holds the whole supply so the airdrop can't compete with lotteries for treasury funds
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"airdrop_token_account".as_ref(),
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump,
+        token::mint = token_mint,
+        token::authority = airdrop_token_account,
+    )]
+    pub airdrop_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// Leaf (index, claimer, amount) of an airdrop in Merkle mode and its proof
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct MerkleClaim {
+    pub index: u64,
+    pub amount: u64,
+    pub proof: Vec<[u8; 32]>,
+}
+
+// Amount approved for the claimer of an airdrop in Signed mode, see verify_claim_signature.
+// The signed message is AIRDROP_CLAIM_MESSAGE_TAG, the program id, then airdrop_id, claimer,
+// amount and expiry, so a signature can't be replayed on another program or message format.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SignedClaim {
+    pub amount: u64,
+    pub expiry: i64,
+}
+
+// How an airdrop is claimed, amount_per_claim only applies in Open mode
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct AirdropConfig {
+    pub amount_per_claim: u64,
+    pub mode: AirdropMode,
+    pub vesting: Option<AirdropVesting>,
+    pub eligibility: Option<AirdropEligibility>,
+}
+
+#[derive(Accounts)]
+#[instruction(airdrop_id: u64, merkle_claim: Option<MerkleClaim>)]
+pub struct ClaimAirdrop<'info> {
+    // Pays the fees and rent, e.g. a relayer sponsoring the user
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    // Owns the tokens and the accounts derived from its key
+    pub user: Signer<'info>,
+
+    // Only for airdrops paid out right away, left out when vesting or claiming into a ticket
+    #[account(
+        init_if_needed,
+        payer = fee_payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"airdrop_token_account".as_ref(),
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub airdrop_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"airdrop".as_ref(), 
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump,
+    )]
+    pub airdrop: Account<'info, Airdrop>,
+
+    // Only for airdrops in Open and Signed mode
+    #[account(
+        init,
+        payer = fee_payer,
+        seeds = [
+            b"airdrop_claimed".as_ref(),
+            airdrop_id.to_le_bytes().as_ref(), 
+            user.key().to_bytes().as_ref(),
+        ],
+        bump,
+        space = 8 + AirdropClaimed::INIT_SPACE,
+    )]
+    pub airdrop_claimed: Option<Account<'info, AirdropClaimed>>,
+
+    // Only for airdrops in Merkle mode, the chunk holding the claimed leaf index
+    #[account(
+        init_if_needed,
+        payer = fee_payer,
+        seeds = [
+            b"airdrop_claim_bitmap".as_ref(),
+            airdrop_id.to_le_bytes().as_ref(),
+            merkle_claim.as_ref().map_or(0, |claim| claim.index / AIRDROP_CLAIM_BITMAP_BITS).to_le_bytes().as_ref(),
+        ],
+        bump,
+        space = 8 + AirdropClaimBitmap::INIT_SPACE,
+    )]
+    pub airdrop_claim_bitmap: Option<Account<'info, AirdropClaimBitmap>>,
+
+    /// CHECK: Only for airdrops in Signed mode, the instructions sysvar holding the Ed25519 signature.
+    #[account(address = instructions_sysvar::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+
+    // Only for airdrops with vesting
+    #[account(
+        init_if_needed,
+        payer = fee_payer,
+        seeds = [
+            b"airdrop_vesting_position".as_ref(),
+            airdrop_id.to_le_bytes().as_ref(),
+            user.key().to_bytes().as_ref(),
+        ],
+        bump,
+        space = 8 + AirdropVestingPosition::INIT_SPACE,
+    )]
+    pub airdrop_vesting_position: Option<Account<'info, AirdropVestingPosition>>,
+
+    // Only for airdrops with eligibility rules, an account of the required mint owned by the claimer
+    pub eligibility_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    // Only for airdrops with eligibility rules and a registration deadline
+    #[account(
+        seeds = [
+            b"airdrop_registration".as_ref(),
+            airdrop_id.to_le_bytes().as_ref(),
+            user.key().to_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub airdrop_registration: Option<Account<'info, AirdropRegistration>>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    airdrop_id: u64,
+    merkle_claim: Option<MerkleClaim>,
+    signed_claim: Option<SignedClaim>,
+    lottery_id: u64,
+    combination: String
+)]
+pub struct ClaimAirdropIntoTicket<'info> {
+    pub claim_airdrop: ClaimAirdrop<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_token_account".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = claim_airdrop.fee_payer,
+        seeds = [
+            b"ticket".as_ref(), 
+            lottery_id.to_le_bytes().as_ref(), 
+            claim_airdrop.user.key().to_bytes().as_ref(),
+            combination.as_ref()
+            ],
+        bump,
+        space = 8 + Ticket::INIT_SPACE,
+    )]
+    pub ticket: Account<'info, Ticket>,
+
+    #[account(
+        init_if_needed,
+        payer = claim_airdrop.fee_payer,
+        seeds = [
+            b"participant".as_ref(), 
+            lottery_id.to_le_bytes().as_ref(), 
+            claim_airdrop.user.key().to_bytes().as_ref(),
+            ],
+        bump,
+        space = 8 + Participant::INIT_SPACE,
+    )]
+    pub participant: Account<'info, Participant>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(airdrop_id: u64)]
+pub struct RegisterForAirdrop<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [
+            b"airdrop".as_ref(), 
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump,
+    )]
+    pub airdrop: Account<'info, Airdrop>,
+
+    pub eligibility_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [
+            b"airdrop_registration".as_ref(),
+            airdrop_id.to_le_bytes().as_ref(),
+            payer.key().to_bytes().as_ref(),
+        ],
+        bump,
+        space = 8 + AirdropRegistration::INIT_SPACE,
+    )]
+    pub airdrop_registration: Account<'info, AirdropRegistration>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(airdrop_id: u64)]
+pub struct WithdrawVestedAirdrop<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"airdrop_token_account".as_ref(),
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub airdrop_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"airdrop_vesting_position".as_ref(),
+            airdrop_id.to_le_bytes().as_ref(),
+            payer.key().to_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub airdrop_vesting_position: Account<'info, AirdropVestingPosition>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(airdrop_id: u64)]
+pub struct UpdateAirdrop<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"airdrop".as_ref(), 
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump,
+    )]
+    pub airdrop: Account<'info, Airdrop>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+}
+
+#[derive(Accounts)]
+#[instruction(airdrop_id: u64)]
+pub struct IncreaseAirdropSupply<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"airdrop".as_ref(), 
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump,
+    )]
+    pub airdrop: Account<'info, Airdrop>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"airdrop_token_account".as_ref(),
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub airdrop_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(airdrop_id: u64)]
+pub struct CancelAirdrop<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"airdrop".as_ref(), 
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump,
+    )]
+    pub airdrop: Account<'info, Airdrop>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"airdrop_token_account".as_ref(),
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub airdrop_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(airdrop_id: u64)]
+pub struct CloseAirdropClaimed<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Only receives the rent, it must match the payer of the claim record.
     #[account(
         mut,
-        seeds = [b"protocol_authority".as_ref()],
+        address = airdrop_claimed.rent_payer
+    )]
+    pub rent_payer: UncheckedAccount<'info>,
+
+    /// CHECK: The airdrop may already be closed, it's deserialized in the handler when it still exists.
+    #[account(
+        seeds = [
+            b"airdrop".as_ref(), 
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump,
+    )]
+    pub airdrop: UncheckedAccount<'info>,
+
+    /// CHECK: Only exists once the airdrop has been closed.
+    #[account(
+        seeds = [
+            b"airdrop_tombstone".as_ref(),
+            airdrop_id.to_le_bytes().as_ref()
+        ],
         bump
     )]
-    pub protocol_authority: Account<'info, ProtocolAuthority>,
+    pub airdrop_tombstone: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [
+            b"airdrop_claimed".as_ref(),
+            airdrop_id.to_le_bytes().as_ref(), 
+            airdrop_claimed.claimer.to_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub airdrop_claimed: Account<'info, AirdropClaimed>,
+
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(airdrop_id: u64)]
-pub struct ClaimAirdrop<'info> {
+#[instruction(airdrop_id: u64, chunk: u64)]
+pub struct CloseAirdropClaimBitmap<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
 
+    /// CHECK: Only receives the rent, it must match the payer of the chunk.
     #[account(
-        init_if_needed,
-        payer = payer,
-        associated_token::mint = token_mint,
-        associated_token::authority = payer,
-        associated_token::token_program = token_program
+        mut,
+        address = airdrop_claim_bitmap.rent_payer
     )]
-    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub rent_payer: UncheckedAccount<'info>,
 
+    /// CHECK: The airdrop may already be closed, it's deserialized in the handler when it still exists.
     #[account(
-        mut,
-        seeds = [b"treasury".as_ref()],
+        seeds = [
+            b"airdrop".as_ref(), 
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump,
+    )]
+    pub airdrop: UncheckedAccount<'info>,
+
+    /// CHECK: Only exists once the airdrop has been closed.
+    #[account(
+        seeds = [
+            b"airdrop_tombstone".as_ref(),
+            airdrop_id.to_le_bytes().as_ref()
+        ],
         bump
     )]
-    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub airdrop_tombstone: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [
+            b"airdrop_claim_bitmap".as_ref(),
+            airdrop_id.to_le_bytes().as_ref(),
+            chunk.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub airdrop_claim_bitmap: Account<'info, AirdropClaimBitmap>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(airdrop_id: u64)]
+pub struct CloseAirdrop<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
 
     #[account(
         mut,
+        close = payer,
         seeds = [
             b"airdrop".as_ref(), 
             airdrop_id.to_le_bytes().as_ref()
@@ -66,26 +580,47 @@ pub struct ClaimAirdrop<'info> {
     )]
     pub airdrop: Account<'info, Airdrop>,
 
+    // Keeps the id from being reused, stale claim records would attach to the new airdrop
     #[account(
         init,
         payer = payer,
         seeds = [
-            b"airdrop_claimed".as_ref(),
-            airdrop_id.to_le_bytes().as_ref(), 
-            payer.key().to_bytes().as_ref(),
+            b"airdrop_tombstone".as_ref(),
+            airdrop_id.to_le_bytes().as_ref()
         ],
         bump,
-        space = 8 + AirdropClaimed::INIT_SPACE,
+        space = 8 + AirdropTombstone::INIT_SPACE,
     )]
-    pub airdrop_claimed: Account<'info, AirdropClaimed>,
+    pub airdrop_tombstone: Account<'info, AirdropTombstone>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"airdrop_token_account".as_ref(),
+            airdrop_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub airdrop_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(mut)]
     pub token_mint: InterfaceAccount<'info, Mint>,
 
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
     pub system_program: Program<'info, System>,
     pub token_program: Interface<'info, TokenInterface>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 pub fn create(
@@ -93,10 +628,11 @@ pub fn create(
     airdrop_id: u64,
     name: String,
     supply: u64,
-    amount_per_claim: u64,
     start_datetime: i64,
     end_datetime: i64,
+    config: AirdropConfig,
 ) -> Result<()> {
+    let AirdropConfig { amount_per_claim, mode, vesting, eligibility } = config;
     require!(
         start_datetime < end_datetime,
         ErrorCode::InvalidAirdropStartEndDatetime
@@ -106,13 +642,44 @@ pub fn create(
         ErrorCode::InvalidAirdropSupply
     );
     require!(
-        amount_per_claim > 0,
-        ErrorCode::InvalidAmount
-    );
-    require!(
-        supply >= amount_per_claim,
-        ErrorCode::InvalidAirdropSupply
+        ctx.accounts.airdrop_tombstone.data_is_empty(),
+        ErrorCode::AirdropIdAlreadyUsed
     );
+    // In Merkle and Signed mode every claim carries its own amount
+    if mode == AirdropMode::Open {
+        require!(
+            amount_per_claim > 0,
+            ErrorCode::InvalidAmount
+        );
+        require!(
+            supply >= amount_per_claim,
+            ErrorCode::InvalidAirdropSupply
+        );
+    }
+
+    if let Some(vesting) = vesting {
+        require!(
+            vesting.vesting_duration > 0
+                && vesting.cliff_duration >= 0
+                && vesting.cliff_duration <= vesting.vesting_duration,
+            ErrorCode::InvalidAirdropVesting
+        );
+    }
+
+    if let Some(eligibility) = &eligibility {
+        require!(
+            eligibility.tiers.len() <= AIRDROP_MAX_BALANCE_TIERS
+                && eligibility.tiers.iter().all(|tier| tier.amount_bps > 0)
+                && eligibility.tiers.windows(2).all(|pair| pair[0].min_balance < pair[1].min_balance),
+            ErrorCode::InvalidAirdropEligibility
+        );
+        if let Some(registration_deadline_slot) = eligibility.registration_deadline_slot {
+            require!(
+                registration_deadline_slot >= Clock::get()?.slot,
+                ErrorCode::InvalidAirdropEligibility
+            );
+        }
+    }
 
     let airdrop = &mut ctx.accounts.airdrop;
     airdrop.airdrop_id = airdrop_id;
@@ -122,16 +689,95 @@ pub fn create(
     airdrop.amount_per_claim = amount_per_claim;
     airdrop.start_datetime = start_datetime;
     airdrop.end_datetime = end_datetime;
+    airdrop.mode = mode;
+    airdrop.vesting = vesting;
+    airdrop.is_paused = false;
+    airdrop.is_cancelled = false;
+    airdrop.eligibility = eligibility;
+
+    let scaled_supply = instructions::utils::get_scaled_amount(
+        supply,
+        ctx.accounts.token_mint.decimals
+    )?;
+
+    pda_owned_token_accounts::withdraw(
+        scaled_supply,
+        &[b"treasury"],
+        ctx.bumps.treasury_token_account,
+        &ctx.accounts.treasury_token_account,
+        &ctx.accounts.airdrop_token_account,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
     Ok(())
 }
 
-pub fn claim(
-    ctx: Context<ClaimAirdrop>,
+// Leaves and inner nodes are domain separated, and pairs are hashed sorted so
+// proofs don't need to say on which side each sibling goes.
+pub fn verify_merkle_proof(merkle_root: [u8; 32], claimer: Pubkey, merkle_claim: &MerkleClaim) -> bool {
+    let mut node = hashv(&[
+        &[0u8],
+        &merkle_claim.index.to_le_bytes(),
+        claimer.as_ref(),
+        &merkle_claim.amount.to_le_bytes(),
+    ]).to_bytes();
+    for sibling in merkle_claim.proof.iter() {
+        node = if node <= *sibling {
+            hashv(&[&[1u8], &node, sibling]).to_bytes()
+        } else {
+            hashv(&[&[1u8], sibling, &node]).to_bytes()
+        };
+    }
+    node == merkle_root
+}
+
+// The claim must come right after an Ed25519 program instruction verifying one
+// signature of `signer` over `message`, with everything inlined in its own data.
+pub fn verify_claim_signature(instructions_sysvar: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<()> {
+    let current_index = instructions_sysvar::load_current_index_checked(instructions_sysvar)?;
+    require!(current_index > 0, ErrorCode::InvalidClaimSignature);
+    let ed25519_ix = instructions_sysvar::load_instruction_at_checked(
+        (current_index - 1) as usize,
+        instructions_sysvar,
+    )?;
+    require!(
+        ed25519_ix.program_id == ed25519_program::ID && ed25519_ix.accounts.is_empty(),
+        ErrorCode::InvalidClaimSignature
+    );
+
+    // Header: signatures count and padding, then the 7 u16 offsets of the signature
+    let data = &ed25519_ix.data;
+    require!(data.len() >= 16 && data[0] == 1, ErrorCode::InvalidClaimSignature);
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let (signature_ix, public_key_offset, public_key_ix) = (read_u16(4), read_u16(6) as usize, read_u16(8));
+    let (message_offset, message_size, message_ix) = (read_u16(10) as usize, read_u16(12) as usize, read_u16(14));
+    require!(
+        signature_ix == u16::MAX && public_key_ix == u16::MAX && message_ix == u16::MAX,
+        ErrorCode::InvalidClaimSignature
+    );
+
+    let public_key = data.get(public_key_offset..public_key_offset + 32);
+    let signed_message = data.get(message_offset..message_offset + message_size);
+    require!(
+        public_key == Some(signer.as_ref()) && signed_message == Some(message),
+        ErrorCode::InvalidClaimSignature
+    );
+    Ok(())
+}
+
+/// Checks a claim against the airdrop mode and eligibility rules and records it,
+/// returning the claimed amount. Paying it out is left to the caller.
+fn record_claim(
+    accounts: &mut ClaimAirdrop,
     airdrop_id: u64,
-) -> Result<()> {
-    let airdrop = &mut ctx.accounts.airdrop;
+    merkle_claim: Option<MerkleClaim>,
+    signed_claim: Option<SignedClaim>,
+) -> Result<u64> {
+    let airdrop = &mut accounts.airdrop;
     let current_timestamp = Clock::get()?.unix_timestamp;
     
+    require!(!airdrop.is_cancelled, ErrorCode::AirdropCancelled);
+    require!(!airdrop.is_paused, ErrorCode::AirdropPaused);
     require!(
         current_timestamp >= airdrop.start_datetime,
         ErrorCode::AirdropNotStarted
@@ -140,33 +786,475 @@ pub fn claim(
         current_timestamp <= airdrop.end_datetime,
         ErrorCode::AirdropEnded
     );
+
+    let claimer = accounts.user.key();
+    let fee_payer = accounts.fee_payer.key();
+    let amount = match (
+        airdrop.mode,
+        merkle_claim,
+        signed_claim,
+        &mut accounts.airdrop_claimed,
+        &mut accounts.airdrop_claim_bitmap,
+    ) {
+        (AirdropMode::Open, None, None, Some(airdrop_claimed), None) => {
+            airdrop_claimed.airdrop_id = airdrop_id;
+            airdrop_claimed.claimer = claimer;
+            airdrop_claimed.rent_payer = fee_payer;
+            airdrop.amount_per_claim
+        }
+        (AirdropMode::Signed { signer }, None, Some(signed_claim), Some(airdrop_claimed), None) => {
+            require!(
+                current_timestamp <= signed_claim.expiry,
+                ErrorCode::ClaimSignatureExpired
+            );
+            let instructions_sysvar = accounts.instructions_sysvar
+                .as_ref()
+                .ok_or(ErrorCode::AirdropModeMismatch)?;
+            let message = [
+                AIRDROP_CLAIM_MESSAGE_TAG,
+                crate::ID.as_ref(),
+                airdrop_id.to_le_bytes().as_ref(),
+                claimer.as_ref(),
+                signed_claim.amount.to_le_bytes().as_ref(),
+                signed_claim.expiry.to_le_bytes().as_ref(),
+            ].concat();
+            verify_claim_signature(instructions_sysvar, &signer, &message)?;
+
+            airdrop_claimed.airdrop_id = airdrop_id;
+            airdrop_claimed.claimer = claimer;
+            airdrop_claimed.rent_payer = fee_payer;
+            signed_claim.amount
+        }
+        (AirdropMode::Merkle { merkle_root }, Some(merkle_claim), None, None, Some(airdrop_claim_bitmap)) => {
+            require!(
+                verify_merkle_proof(merkle_root, claimer, &merkle_claim),
+                ErrorCode::InvalidMerkleProof
+            );
+            require!(
+                !airdrop_claim_bitmap.is_claimed(merkle_claim.index),
+                ErrorCode::AirdropAlreadyClaimed
+            );
+            if airdrop_claim_bitmap.rent_payer == Pubkey::default() {
+                airdrop_claim_bitmap.rent_payer = fee_payer;
+            }
+            airdrop_claim_bitmap.airdrop_id = airdrop_id;
+            airdrop_claim_bitmap.chunk = merkle_claim.index / AIRDROP_CLAIM_BITMAP_BITS;
+            airdrop_claim_bitmap.set_claimed(merkle_claim.index);
+            merkle_claim.amount
+        }
+        _ => return err!(ErrorCode::AirdropModeMismatch),
+    };
     
+    let amount = match &airdrop.eligibility {
+        None => amount,
+        Some(eligibility) => {
+            let eligibility_token_account = accounts.eligibility_token_account
+                .as_ref()
+                .ok_or(ErrorCode::NotEligibleForAirdrop)?;
+            require!(
+                eligibility_token_account.mint == eligibility.required_mint
+                    && eligibility_token_account.owner == claimer,
+                ErrorCode::NotEligibleForAirdrop
+            );
+
+            // The balance has to be held both at registration and at claim time
+            let mut balance = eligibility_token_account.amount;
+            if eligibility.registration_deadline_slot.is_some() {
+                let airdrop_registration = accounts.airdrop_registration
+                    .as_ref()
+                    .ok_or(ErrorCode::NotEligibleForAirdrop)?;
+                balance = balance.min(airdrop_registration.balance);
+            }
+            require!(balance >= eligibility.min_balance, ErrorCode::NotEligibleForAirdrop);
+
+            amount
+                .checked_mul(eligibility.amount_bps(balance))
+                .ok_or(ErrorCode::ArithmeticOverflow)?
+                / 10_000
+        }
+    };
+    require!(amount > 0, ErrorCode::InvalidAmount);
+
     require!(
-        airdrop.supplied.checked_add(airdrop.amount_per_claim).unwrap_or(u64::MAX) <= airdrop.supply,
+        airdrop.supplied.saturating_add(amount) <= airdrop.supply,
         ErrorCode::AirdropSupplyExhausted
     );
-    
+    airdrop.supplied = airdrop.supplied.checked_add(amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    Ok(amount)
+}
+
+pub fn claim(
+    ctx: Context<ClaimAirdrop>,
+    airdrop_id: u64,
+    merkle_claim: Option<MerkleClaim>,
+    signed_claim: Option<SignedClaim>,
+) -> Result<()> {
+    let amount = record_claim(ctx.accounts, airdrop_id, merkle_claim, signed_claim)?;
+    let claimer = ctx.accounts.user.key();
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
     let scaled_amount = instructions::utils::get_scaled_amount(
-        airdrop.amount_per_claim,
+        amount,
         ctx.accounts.token_mint.decimals
     )?;
-    
-    let _ = pda_owned_token_accounts::withdraw(
+
+    match (ctx.accounts.airdrop.vesting, &mut ctx.accounts.airdrop_vesting_position) {
+        (None, None) => {
+            let user_token_account = ctx.accounts.user_token_account
+                .as_ref()
+                .ok_or(ErrorCode::AirdropModeMismatch)?;
+            pda_owned_token_accounts::withdraw(
+                scaled_amount,
+                &[b"airdrop_token_account", &airdrop_id.to_le_bytes()],
+                ctx.bumps.airdrop_token_account,
+                &ctx.accounts.airdrop_token_account,
+                user_token_account,
+                &ctx.accounts.token_mint,
+                &ctx.accounts.token_program,
+            )?;
+        }
+        // Later claims of the same wallet (other Merkle leaves) join the running schedule,
+        // once it ended they would unlock right away so a fresh one starts instead
+        (Some(vesting), Some(airdrop_vesting_position)) => {
+            let schedule_ended = airdrop_vesting_position.is_initialized
+                && current_timestamp >= airdrop_vesting_position.end_datetime;
+            if schedule_ended {
+                require!(
+                    airdrop_vesting_position.withdrawn_amount == airdrop_vesting_position.total_amount,
+                    ErrorCode::AirdropVestingPositionEnded
+                );
+            }
+            if !airdrop_vesting_position.is_initialized || schedule_ended {
+                airdrop_vesting_position.airdrop_id = airdrop_id;
+                airdrop_vesting_position.claimer = claimer;
+                airdrop_vesting_position.total_amount = 0;
+                airdrop_vesting_position.withdrawn_amount = 0;
+                airdrop_vesting_position.start_datetime = current_timestamp;
+                airdrop_vesting_position.cliff_datetime = current_timestamp
+                    .checked_add(vesting.cliff_duration)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?;
+                airdrop_vesting_position.end_datetime = current_timestamp
+                    .checked_add(vesting.vesting_duration)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?;
+                airdrop_vesting_position.is_initialized = true;
+            }
+            airdrop_vesting_position.total_amount = airdrop_vesting_position.total_amount
+                .checked_add(scaled_amount)
+                .ok_or(ErrorCode::ArithmeticOverflow)?;
+        }
+        _ => return err!(ErrorCode::AirdropModeMismatch),
+    }
+
+    Ok(())
+}
+
+pub fn claim_into_ticket(
+    ctx: Context<ClaimAirdropIntoTicket>,
+    airdrop_id: u64,
+    merkle_claim: Option<MerkleClaim>,
+    signed_claim: Option<SignedClaim>,
+    _lottery_id: u64,
+    combination: String,
+) -> Result<()> {
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    lottery_state.sync_status(&Clock::get()?)?;
+    lottery_state.require_open()?;
+    // NFT tickets need their mint and holder accounts, claim first and use buy_lottery_ticket instead
+    require!(!lottery_state.ticket_nft_mode, ErrorCode::TicketNftModeMismatch);
+    lottery::verify_combination(&combination)?;
+
+    let claim_airdrop = &mut ctx.accounts.claim_airdrop;
+    // Vested tokens aren't available yet, they can't pay for a ticket
+    require!(claim_airdrop.airdrop.vesting.is_none(), ErrorCode::AirdropModeMismatch);
+    let amount = record_claim(claim_airdrop, airdrop_id, merkle_claim, signed_claim)?;
+    lottery::verify_amount_on_type(lottery_state, amount)?;
+
+    let ticket = &mut ctx.accounts.ticket;
+    lottery::register_ticket(
+        lottery_state,
+        &mut ctx.accounts.participant,
+        ticket,
+        claim_airdrop.user.key(),
+        claim_airdrop.fee_payer.key(),
+        amount,
+        combination,
+    )?;
+
+    let scaled_amount = instructions::utils::get_scaled_amount(
+        amount,
+        claim_airdrop.token_mint.decimals
+    )?;
+
+    pda_owned_token_accounts::withdraw(
+        scaled_amount,
+        &[b"airdrop_token_account", &airdrop_id.to_le_bytes()],
+        ctx.bumps.claim_airdrop.airdrop_token_account,
+        &claim_airdrop.airdrop_token_account,
+        &ctx.accounts.lottery_token_account,
+        &claim_airdrop.token_mint,
+        &claim_airdrop.token_program,
+    )?;
+
+    msg!(
+        "Airdrop {} claimed into ticket from lottery_id {} with amount {} and combination {}",
+        airdrop_id, ticket.lottery_id, ticket.amount, ticket.combination
+    );
+    Ok(())
+}
+
+pub fn register_balance(ctx: Context<RegisterForAirdrop>, airdrop_id: u64) -> Result<()> {
+    let clock = Clock::get()?;
+    let Some(eligibility) = &ctx.accounts.airdrop.eligibility else {
+        return err!(ErrorCode::AirdropModeMismatch);
+    };
+    let Some(registration_deadline_slot) = eligibility.registration_deadline_slot else {
+        return err!(ErrorCode::AirdropModeMismatch);
+    };
+    require!(clock.slot <= registration_deadline_slot, ErrorCode::AirdropRegistrationClosed);
+
+    let holder = ctx.accounts.payer.key();
+    let eligibility_token_account = &ctx.accounts.eligibility_token_account;
+    require!(
+        eligibility_token_account.mint == eligibility.required_mint
+            && eligibility_token_account.owner == holder,
+        ErrorCode::NotEligibleForAirdrop
+    );
+
+    // Registering again before the deadline replaces the previous balance
+    let airdrop_registration = &mut ctx.accounts.airdrop_registration;
+    airdrop_registration.airdrop_id = airdrop_id;
+    airdrop_registration.holder = holder;
+    airdrop_registration.balance = eligibility_token_account.amount;
+    airdrop_registration.slot = clock.slot;
+
+    msg!(
+        "Balance {} of {} recorded for airdrop_id {}",
+        airdrop_registration.balance, holder, airdrop_id
+    );
+    Ok(())
+}
+
+pub fn withdraw_vested(ctx: Context<WithdrawVestedAirdrop>, airdrop_id: u64) -> Result<()> {
+    let airdrop_vesting_position = &mut ctx.accounts.airdrop_vesting_position;
+    let vested_amount = airdrop_vesting_position.vested_amount(Clock::get()?.unix_timestamp);
+    let amount = vested_amount.saturating_sub(airdrop_vesting_position.withdrawn_amount);
+    require!(amount > 0, ErrorCode::NothingToWithdraw);
+
+    airdrop_vesting_position.withdrawn_amount = vested_amount;
+
+    pda_owned_token_accounts::withdraw(
+        amount,
+        &[b"airdrop_token_account", &airdrop_id.to_le_bytes()],
+        ctx.bumps.airdrop_token_account,
+        &ctx.accounts.airdrop_token_account,
+        &ctx.accounts.payer_token_account,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+
+    msg!(
+        "Released {} vested tokens of airdrop_id {} to {}",
+        amount, airdrop_vesting_position.airdrop_id, airdrop_vesting_position.claimer
+    );
+    Ok(())
+}
+
+pub fn extend(ctx: Context<UpdateAirdrop>, _airdrop_id: u64, end_datetime: i64) -> Result<()> {
+    let airdrop = &mut ctx.accounts.airdrop;
+    require!(!airdrop.is_cancelled, ErrorCode::AirdropCancelled);
+    // Claim records can be closed once the airdrop ended, reopening it would allow claiming twice
+    require!(
+        Clock::get()?.unix_timestamp <= airdrop.end_datetime,
+        ErrorCode::AirdropEnded
+    );
+    require!(
+        end_datetime > airdrop.end_datetime,
+        ErrorCode::InvalidAirdropStartEndDatetime
+    );
+
+    let previous_end_datetime = airdrop.end_datetime;
+    airdrop.end_datetime = end_datetime;
+
+    emit!(AirdropExtended {
+        airdrop_id: airdrop.airdrop_id,
+        previous_end_datetime,
+        end_datetime,
+    });
+    msg!("Airdrop with id {} extended to {}", airdrop.airdrop_id, end_datetime);
+    Ok(())
+}
+
+pub fn add_supply(ctx: Context<IncreaseAirdropSupply>, _airdrop_id: u64, added_supply: u64) -> Result<()> {
+    let airdrop = &mut ctx.accounts.airdrop;
+    require!(!airdrop.is_cancelled, ErrorCode::AirdropCancelled);
+    require!(added_supply > 0, ErrorCode::InvalidAirdropSupply);
+
+    airdrop.supply = airdrop.supply.checked_add(added_supply)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    let scaled_amount = instructions::utils::get_scaled_amount(
+        added_supply,
+        ctx.accounts.token_mint.decimals
+    )?;
+
+    pda_owned_token_accounts::withdraw(
         scaled_amount,
         &[b"treasury"],
         ctx.bumps.treasury_token_account,
         &ctx.accounts.treasury_token_account,
-        &ctx.accounts.payer_token_account,
+        &ctx.accounts.airdrop_token_account,
         &ctx.accounts.token_mint,
         &ctx.accounts.token_program,
+    )?;
+
+    emit!(AirdropSupplyIncreased {
+        airdrop_id: airdrop.airdrop_id,
+        added_supply,
+        supply: airdrop.supply,
+    });
+    msg!("Airdrop with id {} supply increased to {}", airdrop.airdrop_id, airdrop.supply);
+    Ok(())
+}
+
+pub fn set_paused(ctx: Context<UpdateAirdrop>, _airdrop_id: u64, is_paused: bool) -> Result<()> {
+    let airdrop = &mut ctx.accounts.airdrop;
+    require!(!airdrop.is_cancelled, ErrorCode::AirdropCancelled);
+    airdrop.is_paused = is_paused;
+
+    emit!(AirdropPauseUpdated {
+        airdrop_id: airdrop.airdrop_id,
+        is_paused,
+    });
+    msg!("Airdrop with id {} paused: {}", airdrop.airdrop_id, is_paused);
+    Ok(())
+}
+
+pub fn terminate(ctx: Context<CancelAirdrop>, airdrop_id: u64) -> Result<()> {
+    let airdrop = &mut ctx.accounts.airdrop;
+    require!(!airdrop.is_cancelled, ErrorCode::AirdropCancelled);
+
+    let unused_amount = airdrop.supply - airdrop.supplied;
+    let scaled_amount = instructions::utils::get_scaled_amount(
+        unused_amount,
+        ctx.accounts.token_mint.decimals
+    )?;
+    if scaled_amount > 0 {
+        pda_owned_token_accounts::withdraw(
+            scaled_amount,
+            &[b"airdrop_token_account", &airdrop_id.to_le_bytes()],
+            ctx.bumps.airdrop_token_account,
+            &ctx.accounts.airdrop_token_account,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+        )?;
+    }
+
+    // The airdrop ends right away so it can be closed, the vault only keeps what vesting positions still owe
+    airdrop.is_cancelled = true;
+    airdrop.supply = airdrop.supplied;
+    airdrop.end_datetime = airdrop.end_datetime.min(Clock::get()?.unix_timestamp);
+
+    emit!(AirdropCancelled {
+        airdrop_id,
+        supplied: airdrop.supplied,
+        unused_amount,
+    });
+    msg!(
+        "Airdrop with id {} cancelled, {} unused tokens returned to the treasury",
+        airdrop_id, unused_amount
+    );
+    Ok(())
+}
+
+pub fn close_claimed(ctx: Context<CloseAirdropClaimed>, _airdrop_id: u64) -> Result<()> {
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
+    require!(
+        airdrop_ended(&ctx.accounts.airdrop, &ctx.accounts.airdrop_tombstone, current_timestamp)?,
+        ErrorCode::AirdropNotEnded
+    );
+
+    msg!(
+        "Airdrop claim of {} for airdrop_id {} closed",
+        ctx.accounts.airdrop_claimed.claimer, ctx.accounts.airdrop_claimed.airdrop_id
+    );
+    Ok(())
+}
+
+pub fn close_claim_bitmap(ctx: Context<CloseAirdropClaimBitmap>, _airdrop_id: u64, _chunk: u64) -> Result<()> {
+    let current_timestamp = Clock::get()?.unix_timestamp;
+    require!(
+        airdrop_ended(&ctx.accounts.airdrop, &ctx.accounts.airdrop_tombstone, current_timestamp)?,
+        ErrorCode::AirdropNotEnded
+    );
+
+    msg!(
+        "Airdrop claim bitmap chunk {} for airdrop_id {} closed",
+        ctx.accounts.airdrop_claim_bitmap.chunk, ctx.accounts.airdrop_claim_bitmap.airdrop_id
+    );
+    Ok(())
+}
+
+// An ended airdrop can't be extended, and the tombstone is left behind once it's closed
+fn airdrop_ended(
+    airdrop: &UncheckedAccount,
+    airdrop_tombstone: &UncheckedAccount,
+    current_timestamp: i64,
+) -> Result<bool> {
+    if !airdrop_tombstone.data_is_empty() {
+        return Ok(true);
+    }
+    let airdrop_data = airdrop.try_borrow_data()?;
+    Ok(!airdrop_data.is_empty()
+        && current_timestamp > Airdrop::try_deserialize(&mut &airdrop_data[..])?.end_datetime)
+}
+
+pub fn close_state(ctx: Context<CloseAirdrop>, airdrop_id: u64) -> Result<()> {
+    let airdrop = &ctx.accounts.airdrop;
+    require!(
+        Clock::get()?.unix_timestamp > airdrop.end_datetime,
+        ErrorCode::AirdropNotEnded
     );
-    
-    airdrop.supplied = airdrop.supplied.checked_add(airdrop.amount_per_claim)
-        .ok_or(ErrorCode::ArithmeticOverflow)?;
-    
-    let airdrop_claimed = &mut ctx.accounts.airdrop_claimed;
-    airdrop_claimed.airdrop_id = airdrop_id;
-    airdrop_claimed.claimer = ctx.accounts.payer.key();
 
+    let airdrop_id_bytes = airdrop_id.to_le_bytes();
+    let vault_seeds: &[&[u8]] = &[b"airdrop_token_account", &airdrop_id_bytes];
+    // Tokens of vesting positions stay in the vault until their claimers withdraw them
+    let remainder = match airdrop.vesting {
+        None => ctx.accounts.airdrop_token_account.amount,
+        Some(_) => instructions::utils::get_scaled_amount(
+            airdrop.supply - airdrop.supplied,
+            ctx.accounts.token_mint.decimals
+        )?,
+    };
+
+    if remainder > 0 {
+        pda_owned_token_accounts::withdraw(
+            remainder,
+            vault_seeds,
+            ctx.bumps.airdrop_token_account,
+            &ctx.accounts.airdrop_token_account,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+        )?;
+    }
+    if airdrop.vesting.is_none() {
+        pda_owned_token_accounts::close_token_account(
+            vault_seeds,
+            ctx.bumps.airdrop_token_account,
+            &ctx.accounts.airdrop_token_account,
+            ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.token_program,
+        )?;
+    }
+
+    let airdrop_tombstone = &mut ctx.accounts.airdrop_tombstone;
+    airdrop_tombstone.airdrop_id = airdrop_id;
+    airdrop_tombstone.closed_at = Clock::get()?.unix_timestamp;
+
+    msg!("Airdrop with id {} closed, {} tokens returned to the treasury", airdrop.airdrop_id, remainder);
     Ok(())
-}
\ No newline at end of file
+}
@@ -2,10 +2,13 @@ use anchor_lang::prelude::*;
 use anchor_spl::{associated_token::AssociatedToken, token_interface::{ Mint, TokenInterface, TokenAccount}};
 
 use crate::state::protocol_authority::ProtocolAuthority;
-use crate::state::six_month_cliff::SixMonthCliff;
+use crate::instructions::vesting_schedule::{VestingRelease, VestingSchedule, VestingScheduleParams};
 
 pub use crate::instructions;
-pub use crate::constants::{TREASURY_INITIAL_AMOUNT, SIX_MONTH_CLIFF_AMOUNT};
+pub use crate::constants::{
+    TREASURY_INITIAL_AMOUNT, TREASURY_VESTING_SCHEDULE_ID, TREASURY_VESTING_PERIOD,
+    TREASURY_VESTING_PERIOD_AMOUNT, TREASURY_VESTING_PERIODS,
+};
 pub use crate::state::protocol_error::ErrorCode;
 #[derive(Accounts)]
 pub struct InitializeTreasury<'info> {
@@ -31,24 +34,32 @@ pub struct InitializeTreasury<'info> {
     )]
     pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
 
+    // Allocation of the authority, released every TREASURY_VESTING_PERIOD through release_vested
     #[account(
         init,
-        seeds = [b"six_month_cliff_treasury".as_ref()],
-        bump,
         payer = payer,
-        token::mint = token_mint,
-        token::authority = six_month_cliff_token_account,
+        seeds = [
+            b"vesting_schedule".as_ref(),
+            payer.key().to_bytes().as_ref(),
+            TREASURY_VESTING_SCHEDULE_ID.to_le_bytes().as_ref()
+        ],
+        bump,
+        space = 8 + VestingSchedule::INIT_SPACE,
     )]
-    pub six_month_cliff_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub vesting_schedule: Account<'info, VestingSchedule>,
 
     #[account(
         init,
         payer = payer,
-        space = 8 + SixMonthCliff::INIT_SPACE,
-        seeds = [b"six_month_cliff_state".as_ref()],
-        bump
+        seeds = [
+            b"vesting_schedule_token_account".as_ref(),
+            vesting_schedule.key().as_ref()
+        ],
+        bump,
+        token::mint = token_mint,
+        token::authority = vesting_schedule_token_account,
     )]
-    pub six_month_cliff_state: Account<'info, SixMonthCliff>,
+    pub vesting_schedule_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(mut)]
     pub token_mint: InterfaceAccount<'info, Mint>,
@@ -86,25 +97,40 @@ pub fn contribute_treasury_account(ctx: &Context<InitializeTreasury>) -> Result<
     Ok(())
 }
 
-pub fn contribute_six_month_cliff_account(ctx: Context<InitializeTreasury>) -> Result<()> {
-    msg!("Contributing {} DRACO to the six month cliff", SIX_MONTH_CLIFF_AMOUNT * 3 );
-
-    let scaled_amount = instructions::utils::get_scaled_amount(
-        SIX_MONTH_CLIFF_AMOUNT * 3,
-        ctx.accounts.token_mint.decimals
+pub fn create_treasury_vesting_schedule(ctx: Context<InitializeTreasury>) -> Result<()> {
+    let payer = ctx.accounts.payer.key();
+    let start_datetime = Clock::get()?.unix_timestamp;
+    let params = VestingScheduleParams {
+        beneficiary: payer,
+        total_amount: TREASURY_VESTING_PERIOD_AMOUNT * TREASURY_VESTING_PERIODS,
+        start_datetime,
+        cliff_datetime: start_datetime,
+        release: VestingRelease::Periodic {
+            period: TREASURY_VESTING_PERIOD,
+            amount_per_period: TREASURY_VESTING_PERIOD_AMOUNT,
+        },
+        from_treasury: false,
+        revocable: false,
+    };
+    msg!("Vesting {} DRACO for the authority", params.total_amount);
+
+    let scaled_amount = instructions::vesting_schedule::init_schedule(
+        &mut ctx.accounts.vesting_schedule,
+        TREASURY_VESTING_SCHEDULE_ID,
+        payer,
+        &params,
+        ctx.accounts.token_mint.decimals,
     )?;
 
-    let _ = instructions::pda_owned_token_accounts::contribute(
+    instructions::pda_owned_token_accounts::contribute(
         scaled_amount,
         &ctx.accounts.payer_token_account,
-        &ctx.accounts.six_month_cliff_token_account,
+        &ctx.accounts.vesting_schedule_token_account,
         &ctx.accounts.payer,
         &ctx.accounts.token_mint,
         &ctx.accounts.token_program,
-    );
-    ctx.accounts.six_month_cliff_state.last_transfer_out_datetime = Clock::get()?.unix_timestamp;
-    ctx.accounts.six_month_cliff_state.transfers_performed = 0;
-    msg!("Six month cliff account contributed successfully");
+    )?;
+    msg!("Treasury vesting schedule created successfully");
 
     Ok(())
 }
\ No newline at end of file
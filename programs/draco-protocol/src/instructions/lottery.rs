@@ -1,15 +1,18 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
 
 use anchor_spl::{associated_token::AssociatedToken, token_interface::{ Mint, TokenInterface, TokenAccount}};
 use switchboard_on_demand::RandomnessAccountData;
 
 use crate::{instructions, state::protocol_authority::ProtocolAuthority};
-use crate::state::lottery::LotteryState;
+use crate::state::lottery::{LotteryState, LotteryStatus};
 use crate::state::lottery_reward_factors::LotteryRewardFactors;
 use crate::state::ticket::Ticket;
+use crate::state::participant::Participant;
+use crate::state::lottery_tombstone::LotteryTombstone;
 use crate::state::protocol_error::ErrorCode;
 
-use crate::instructions::pda_owned_token_accounts;
+use crate::instructions::{pda_owned_token_accounts, ticket_nft};
 
 use crate::constants::{
     VALID_SUITS, VALID_VALUES, COMBINATION_LENGTH, PAY_LOTTERY_TYPE, LOCK_LOTTERY_TYPE,
@@ -54,6 +57,16 @@ pub struct StartLottery<'info> {
     )]
     pub lottery_state: Account<'info, LotteryState>,
 
+    /// CHECK: Must be empty, it only exists if a lottery with this id was already closed.
+    #[account(
+        seeds = [
+            b"lottery_tombstone".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_tombstone: UncheckedAccount<'info>,
+
     #[account(mut)]
     pub token_mint: InterfaceAccount<'info, Mint>,
 
@@ -72,17 +85,21 @@ pub struct StartLottery<'info> {
 #[derive(Accounts)]
 #[instruction(lottery_id: u64, combination: String)]
 pub struct BuyLotteryTicket<'info> {
+    // Pays the fees and rent, e.g. a relayer sponsoring the user
     #[account(mut)]
-    pub payer: Signer<'info>,
+    pub fee_payer: Signer<'info>,
+
+    // Owns the tokens and the accounts derived from its key
+    pub user: Signer<'info>,
 
     #[account(
         init_if_needed,
-        payer = payer,
+        payer = fee_payer,
         associated_token::mint = token_mint,
-        associated_token::authority = payer,
+        associated_token::authority = user,
         associated_token::token_program = token_program
     )]
-    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
@@ -96,11 +113,11 @@ pub struct BuyLotteryTicket<'info> {
 
     #[account(
         init_if_needed,
-        payer = payer,
+        payer = fee_payer,
         seeds = [
             b"ticket".as_ref(), 
             lottery_id.to_le_bytes().as_ref(), 
-            payer.key().to_bytes().as_ref(),
+            user.key().to_bytes().as_ref(),
             combination.as_ref()
             ],
         bump,
@@ -108,6 +125,19 @@ pub struct BuyLotteryTicket<'info> {
     )]
     pub ticket: Account<'info, Ticket>,
 
+    #[account(
+        init_if_needed,
+        payer = fee_payer,
+        seeds = [
+            b"participant".as_ref(), 
+            lottery_id.to_le_bytes().as_ref(), 
+            user.key().to_bytes().as_ref(),
+            ],
+        bump,
+        space = 8 + Participant::INIT_SPACE,
+    )]
+    pub participant: Account<'info, Participant>,
+
     #[account(
         mut,
         seeds = [
@@ -118,6 +148,32 @@ pub struct BuyLotteryTicket<'info> {
     )]
     pub lottery_state: Account<'info, LotteryState>,
 
+    // Only for lotteries in ticket NFT mode
+    #[account(
+        init_if_needed,
+        payer = fee_payer,
+        seeds = [
+            b"ticket_mint".as_ref(), 
+            ticket.key().as_ref()
+        ],
+        bump,
+        mint::decimals = 0,
+        mint::authority = ticket_mint,
+        mint::token_program = token_program,
+        extensions::metadata_pointer::authority = ticket_mint,
+        extensions::metadata_pointer::metadata_address = ticket_mint,
+    )]
+    pub ticket_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = fee_payer,
+        associated_token::mint = ticket_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub ticket_holder_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
     #[account(mut)]
     pub token_mint: InterfaceAccount<'info, Mint>,
     
@@ -127,6 +183,47 @@ pub struct BuyLotteryTicket<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct CloseLotterySales<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct FinalizeLotteryTicketSet<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+}
+
 #[derive(Accounts)]
 #[instruction(lottery_id: u64)]
 pub struct CommitLotteryRandomness<'info> {
@@ -188,17 +285,21 @@ pub struct RevealLotteryRandomness<'info> {
 #[derive(Accounts)]
 #[instruction(lottery_id: u64, combination: String)]
 pub struct ClaimLotteryPrizeForCombination<'info> {
+    // Pays the fees and rent, e.g. a relayer sponsoring the user
     #[account(mut)]
-    pub payer: Signer<'info>,
+    pub fee_payer: Signer<'info>,
+
+    // Owns the tokens and the accounts derived from its key
+    pub user: Signer<'info>,
 
     #[account(
         init_if_needed,
-        payer = payer,
+        payer = fee_payer,
         associated_token::mint = token_mint,
-        associated_token::authority = payer,
+        associated_token::authority = user,
         associated_token::token_program = token_program
     )]
-    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
@@ -222,7 +323,7 @@ pub struct ClaimLotteryPrizeForCombination<'info> {
         seeds = [
             b"ticket".as_ref(), 
             lottery_id.to_le_bytes().as_ref(), 
-            payer.key().to_bytes().as_ref(),
+            ticket.original_participant.to_bytes().as_ref(),
             combination.as_ref()
             ],
         bump,
@@ -246,6 +347,20 @@ pub struct ClaimLotteryPrizeForCombination<'info> {
     )]
     pub lottery_reward_factors: Account<'info, LotteryRewardFactors>,
 
+    // Only for lotteries in ticket NFT mode, the payer must hold the ticket NFT
+    #[account(
+        mut,
+        seeds = [
+            b"ticket_mint".as_ref(), 
+            ticket.key().as_ref()
+        ],
+        bump,
+    )]
+    pub ticket_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub ticket_holder_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
     #[account(mut)]
     pub token_mint: InterfaceAccount<'info, Mint>,
 
@@ -255,6 +370,151 @@ pub struct ClaimLotteryPrizeForCombination<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, combination: String)]
+pub struct PreviewPrize<'info> {
+    #[account(
+        seeds = [
+            b"ticket".as_ref(), 
+            lottery_id.to_le_bytes().as_ref(), 
+            ticket.original_participant.to_bytes().as_ref(),
+            combination.as_ref()
+            ],
+        bump,
+    )]
+    pub ticket: Account<'info, Ticket>,
+
+    #[account(
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(
+        seeds =[
+            b"lottery_token_account".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"lottery_reward_factors".as_ref()],
+        bump
+    )]
+    pub lottery_reward_factors: Account<'info, LotteryRewardFactors>,
+
+    pub token_mint: InterfaceAccount<'info, Mint>,
+}
+
+// The tickets to claim are passed as writable remaining accounts
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct ClaimLotteryPrizes<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds =[
+            b"lottery_token_account".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(
+        seeds = [b"lottery_reward_factors".as_ref()],
+        bump
+    )]
+    pub lottery_reward_factors: Account<'info, LotteryRewardFactors>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct ExpireUnclaimedPrizes<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct ExtendLotteryClaimDeadline<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+}
+
 #[derive(Accounts)]
 #[instruction(lottery_id: u64)]
 pub struct CloseLottery<'info> {
@@ -304,35 +564,404 @@ pub struct CloseLottery<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
-pub fn start(
-    ctx: Context<StartLottery>, 
-    lottery_id: u64, 
-    lottery_name: String, 
-    lottery_description: String, 
-    lottery_type: u8, 
-    lottery_start_datetime: i64, 
-    lottery_end_datetime: i64, 
-    initial_prize_pool: u64, 
-    min_tokens_per_participant: u64
-) -> Result<()> {
-    msg!("Starting lottery");
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct CancelLottery<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
 
-    require!(
-        lottery_type == PAY_LOTTERY_TYPE || lottery_type == LOCK_LOTTERY_TYPE, 
-        ErrorCode::InvalidLotteryType
-    );
-    require!(
-        lottery_start_datetime < lottery_end_datetime, 
-        ErrorCode::InvalidLotteryStartEndDatetime
-    );
-    require!(
-        initial_prize_pool > 0, 
-        ErrorCode::InvalidInitialPrizePool
-    );
-    require!(
-        min_tokens_per_participant > 0, 
-        ErrorCode::InvalidMinTokensPerParticipant
-    );
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(
+        mut,
+        seeds =[
+            b"lottery_token_account".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, combination: String)]
+pub struct RefundTicket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: The ticket owner, only checked not to be a program account in the handler.
+    #[account(address = ticket.participant)]
+    pub participant: UncheckedAccount<'info>,
+
+    /// CHECK: Only receives the ticket rent, it must match the payer of the ticket.
+    #[account(
+        mut,
+        address = ticket.rent_payer
+    )]
+    pub rent_payer: UncheckedAccount<'info>,
+
+    /// CHECK: Only receives the participant rent, it must match the payer of the participant.
+    #[account(
+        mut,
+        address = participant_account.rent_payer
+    )]
+    pub participant_rent_payer: UncheckedAccount<'info>,
+
+    /// CHECK: Receives the refund. Validated in the handler against the participant,
+    /// or against the payer holding the ticket NFT in ticket NFT mode.
+    pub recipient: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = recipient,
+        associated_token::token_program = token_program
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds =[
+            b"lottery_token_account".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [
+            b"ticket".as_ref(), 
+            lottery_id.to_le_bytes().as_ref(), 
+            ticket.original_participant.to_bytes().as_ref(),
+            combination.as_ref()
+            ],
+        bump,
+    )]
+    pub ticket: Account<'info, Ticket>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    // Participant of the ticket owner, closed once its last ticket is refunded
+    #[account(
+        mut,
+        seeds = [
+            b"participant".as_ref(), 
+            lottery_id.to_le_bytes().as_ref(), 
+            ticket.participant.to_bytes().as_ref(),
+            ],
+        bump,
+    )]
+    pub participant_account: Account<'info, Participant>,
+
+    // Only for lotteries in ticket NFT mode, the payer must hold the ticket NFT
+    #[account(
+        mut,
+        seeds = [
+            b"ticket_mint".as_ref(), 
+            ticket.key().as_ref()
+        ],
+        bump,
+    )]
+    pub ticket_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub ticket_holder_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct UpdateScheduledLottery<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct TopUpLotteryPrizePool<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_token_account".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, combination: String)]
+pub struct CloseLotteryTicket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Only receives the ticket rent, it must match the payer of the ticket.
+    #[account(
+        mut,
+        address = ticket.rent_payer
+    )]
+    pub rent_payer: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = rent_payer,
+        seeds = [
+            b"ticket".as_ref(), 
+            lottery_id.to_le_bytes().as_ref(), 
+            ticket.original_participant.to_bytes().as_ref(),
+            combination.as_ref()
+            ],
+        bump,
+    )]
+    pub ticket: Account<'info, Ticket>,
+
+    /// CHECK: The lottery state may already be closed, it's deserialized in the handler when it still exists.
+    #[account(
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: UncheckedAccount<'info>,
+
+    /// CHECK: Only exists once the lottery accounts have been closed.
+    #[account(
+        seeds = [
+            b"lottery_tombstone".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_tombstone: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct CloseLotteryAccounts<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        close = payer,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(
+        mut,
+        seeds =[
+            b"lottery_token_account".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    // Keeps the id from being reused, stale tickets and participants would attach to the new lottery
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"lottery_tombstone".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump,
+        space = 8 + LotteryTombstone::INIT_SPACE,
+    )]
+    pub lottery_tombstone: Account<'info, LotteryTombstone>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// Optional settings of a lottery, the defaults are no caps, no sales close buffer,
+// plain tickets and a claim deadline LOTTERY_CLOSE_TIME_BUFFER after the end
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct LotteryConfig {
+    pub max_participants: Option<u64>,
+    // In ticket units, see LotteryState::ticket_units
+    pub max_tickets_per_wallet: Option<u64>,
+    // Seconds before the end datetime when ticket sales close
+    pub sales_close_buffer: i64,
+    pub ticket_nft_mode: bool,
+    pub claim_deadline: Option<i64>,
+}
+
+pub fn start(
+    ctx: Context<StartLottery>, 
+    lottery_id: u64, 
+    lottery_name: String, 
+    lottery_description: String, 
+    lottery_type: u8, 
+    lottery_start_datetime: i64, 
+    lottery_end_datetime: i64, 
+    initial_prize_pool: u64, 
+    min_tokens_per_participant: u64,
+    config: LotteryConfig,
+) -> Result<()> {
+    msg!("Starting lottery");
+    let LotteryConfig {
+        max_participants,
+        max_tickets_per_wallet,
+        sales_close_buffer,
+        ticket_nft_mode,
+        claim_deadline,
+    } = config;
+
+    require!(
+        lottery_type == PAY_LOTTERY_TYPE || lottery_type == LOCK_LOTTERY_TYPE, 
+        ErrorCode::InvalidLotteryType
+    );
+    require!(
+        lottery_start_datetime < lottery_end_datetime, 
+        ErrorCode::InvalidLotteryStartEndDatetime
+    );
+    require!(
+        sales_close_buffer >= 0
+            && lottery_start_datetime < lottery_end_datetime.saturating_sub(sales_close_buffer),
+        ErrorCode::InvalidSalesCloseBuffer
+    );
+    require!(
+        initial_prize_pool > 0, 
+        ErrorCode::InvalidInitialPrizePool
+    );
+    require!(
+        min_tokens_per_participant > 0, 
+        ErrorCode::InvalidMinTokensPerParticipant
+    );
+    require!(
+        max_participants != Some(0) && max_tickets_per_wallet != Some(0),
+        ErrorCode::InvalidLotteryLimits
+    );
+    require!(
+        ctx.accounts.lottery_tombstone.data_is_empty(),
+        ErrorCode::LotteryIdAlreadyUsed
+    );
+    let claim_deadline = match claim_deadline {
+        Some(claim_deadline) => claim_deadline,
+        None => lottery_end_datetime
+            .checked_add(LOTTERY_CLOSE_TIME_BUFFER)
+            .ok_or(ErrorCode::ArithmeticOverflow)?,
+    };
+    require!(
+        claim_deadline > lottery_end_datetime,
+        ErrorCode::ClaimDeadlineBeforeLotteryEnd
+    );
 
     let lottery_state = &mut ctx.accounts.lottery_state;
     lottery_state.lottery_id = lottery_id;
@@ -341,13 +970,27 @@ pub fn start(
     lottery_state.lottery_type = lottery_type;
     lottery_state.lottery_start_datetime = lottery_start_datetime;
     lottery_state.lottery_end_datetime = lottery_end_datetime;
+    lottery_state.sales_close_buffer = sales_close_buffer;
+    lottery_state.sales_closed_slot = 0;
     lottery_state.initial_prize_pool = initial_prize_pool;
     lottery_state.accumulated_prize_pool = initial_prize_pool;
+    lottery_state.sponsored_prize_pool = 0;
     lottery_state.min_tokens_per_participant = min_tokens_per_participant;
     lottery_state.winning_combination = None;
+    lottery_state.drawn_datetime = 0;
     lottery_state.participants_count = 0;
+    lottery_state.max_participants = max_participants;
+    lottery_state.max_tickets_per_wallet = max_tickets_per_wallet;
+    lottery_state.ticket_nft_mode = ticket_nft_mode;
     lottery_state.randomness_account = Pubkey::default();
-    lottery_state.is_closed = false;
+    lottery_state.claim_deadline = claim_deadline;
+    lottery_state.expired_unclaimed_amount = None;
+    lottery_state.claimed_prize_amount = 0;
+    lottery_state.ticket_set_hash = [0; 32];
+    lottery_state.ticket_set_entries = 0;
+    lottery_state.ticket_set_finalized = false;
+    lottery_state.status = LotteryStatus::Scheduled;
+    lottery_state.sync_status(&Clock::get()?)?;
 
     let scaled_amount = instructions::utils::get_scaled_amount(
         initial_prize_pool,
@@ -368,25 +1011,14 @@ pub fn start(
     Ok(())
 }
 
-pub fn enforce_lottery_active(ctx: &Context<BuyLotteryTicket>) -> Result<()> {
+pub fn enforce_lottery_active(ctx: &mut Context<BuyLotteryTicket>) -> Result<()> {
     msg!("Enforcing lottery active");
-    let lottery_state = &ctx.accounts.lottery_state;
+    let lottery_state = &mut ctx.accounts.lottery_state;
     msg!("Lottery start date {}", lottery_state.lottery_start_datetime);
     msg!("Lottery end date {}", lottery_state.lottery_end_datetime);
     msg!("Current time {}",Clock::get()?.unix_timestamp);
-    require!(
-        lottery_state.lottery_end_datetime > Clock::get()?.unix_timestamp, 
-        ErrorCode::LotteryFinished
-    );
-    require!(
-        lottery_state.lottery_start_datetime < Clock::get()?.unix_timestamp, 
-        ErrorCode::LotteryNotStarted
-    );
-    require!(
-        !lottery_state.is_closed, 
-        ErrorCode::LotteryClosed
-    );
-    Ok(())
+    lottery_state.sync_status(&Clock::get()?)?;
+    lottery_state.require_open()
 }
 
 pub fn verify_combination(combination: &String) -> Result<Vec<(char, char)>> {
@@ -411,10 +1043,10 @@ pub fn verify_combination(combination: &String) -> Result<Vec<(char, char)>> {
     Ok(cards)
 }
 
-pub fn verify_amount_on_type(ctx: &Context<BuyLotteryTicket>, amount: u64) -> Result<()> {
+pub fn verify_amount_on_type(lottery_state: &LotteryState, amount: u64) -> Result<()> {
     msg!("Verifying amount on type");
-    let lottery_type = ctx.accounts.lottery_state.lottery_type;
-    let min_tokens_per_participant = ctx.accounts.lottery_state.min_tokens_per_participant;
+    let lottery_type = lottery_state.lottery_type;
+    let min_tokens_per_participant = lottery_state.min_tokens_per_participant;
     if lottery_type == PAY_LOTTERY_TYPE {
         require!(
                amount >= min_tokens_per_participant 
@@ -429,26 +1061,63 @@ pub fn verify_amount_on_type(ctx: &Context<BuyLotteryTicket>, amount: u64) -> Re
 
 pub fn buy_ticket(
     ctx: Context<BuyLotteryTicket>, 
-    lottery_id: u64, 
+    _lottery_id: u64, 
     amount: u64, 
     combination: String
 ) -> Result<()> {
     msg!("Buying ticket");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    require!(
+        lottery_state.ticket_nft_mode
+            == (ctx.accounts.ticket_mint.is_some() && ctx.accounts.ticket_holder_account.is_some()),
+        ErrorCode::TicketNftModeMismatch
+    );
+
     let ticket = &mut ctx.accounts.ticket;
-    if !ticket.is_initialized {
-        ticket.lottery_id = lottery_id;
-        ticket.combination = combination;
-        ticket.amount = amount;
-        ticket.participant = ctx.accounts.payer.key();
-        ticket.is_claimed = false;
-        ticket.is_initialized = true;
-    } else {
-        ticket.amount += amount;
+    let ticket_key = ticket.key();
+    let is_new_ticket = !ticket.is_initialized;
+    // In ticket NFT mode the ticket belongs to the NFT holder, the buyer may have sold it
+    if lottery_state.ticket_nft_mode && !is_new_ticket {
+        ticket_nft::enforce_ticket_nft_holder(
+            &ctx.accounts.ticket_mint,
+            &ctx.accounts.ticket_holder_account,
+            ctx.accounts.user.key(),
+        )?;
     }
+    register_ticket(
+        lottery_state,
+        &mut ctx.accounts.participant,
+        ticket,
+        ctx.accounts.user.key(),
+        ctx.accounts.fee_payer.key(),
+        amount,
+        combination,
+    )?;
 
-    let lottery_state = &mut ctx.accounts.lottery_state;
-    lottery_state.participants_count += 1;
-    lottery_state.accumulated_prize_pool += amount;
+    if let (Some(ticket_mint), Some(ticket_mint_bump)) = (&ctx.accounts.ticket_mint, ctx.bumps.ticket_mint) {
+        if is_new_ticket {
+            if let Some(ticket_holder_account) = &ctx.accounts.ticket_holder_account {
+                ticket_nft::mint_ticket_nft(
+                    ticket,
+                    ticket_mint_bump,
+                    ticket_mint,
+                    ticket_holder_account,
+                    &ctx.accounts.fee_payer,
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.system_program,
+                )?;
+            }
+        } else {
+            ticket_nft::update_ticket_nft_field(
+                "amount".to_string(),
+                ticket.amount.to_string(),
+                ticket_key,
+                ticket_mint_bump,
+                ticket_mint,
+                &ctx.accounts.token_program,
+            )?;
+        }
+    }
 
     let scaled_amount = instructions::utils::get_scaled_amount(
         amount,
@@ -457,9 +1126,9 @@ pub fn buy_ticket(
 
     let _ = pda_owned_token_accounts::contribute(
         scaled_amount, 
-        &ctx.accounts.payer_token_account, 
+        &ctx.accounts.user_token_account, 
         &ctx.accounts.lottery_token_account, 
-        &ctx.accounts.payer, 
+        &ctx.accounts.user, 
         &ctx.accounts.token_mint, 
         &ctx.accounts.token_program
     );
@@ -470,6 +1139,191 @@ pub fn buy_ticket(
     Ok(())
 }
 
+/// Ticket bookkeeping shared by every way of buying a ticket, moving the tokens
+/// into the lottery vault is left to the caller.
+pub fn register_ticket(
+    lottery_state: &mut LotteryState,
+    participant: &mut Participant,
+    ticket: &mut Ticket,
+    owner: Pubkey,
+    rent_payer: Pubkey,
+    amount: u64,
+    combination: String,
+) -> Result<()> {
+    let previous_amount = if ticket.is_initialized { ticket.amount } else { 0 };
+    let ticket_amount = previous_amount
+        .checked_add(amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    let units = lottery_state.ticket_units(ticket_amount) - lottery_state.ticket_units(previous_amount);
+    add_ticket_units(lottery_state, participant, owner, rent_payer, units)?;
+
+    if !ticket.is_initialized {
+        ticket.lottery_id = lottery_state.lottery_id;
+        ticket.combination = combination;
+        ticket.amount = amount;
+        ticket.participant = owner;
+        ticket.original_participant = owner;
+        ticket.rent_payer = rent_payer;
+        ticket.is_claimed = false;
+        ticket.is_initialized = true;
+    } else {
+        // While listed on the marketplace the ticket is escrowed and can't be topped up,
+        // in ticket NFT mode the caller checks the NFT holder instead
+        require!(
+            lottery_state.ticket_nft_mode || ticket.participant == owner,
+            ErrorCode::NotTicketOwner
+        );
+        ticket.amount = ticket_amount;
+    }
+
+    participant.total_spent = participant.total_spent
+        .checked_add(amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    lottery_state.accumulated_prize_pool += amount;
+    record_ticket_entry(lottery_state, &ticket.participant, &ticket.combination, amount);
+    Ok(())
+}
+
+/// Gives ticket units to a wallet, enforcing the participant and per wallet caps.
+pub fn add_ticket_units(
+    lottery_state: &mut LotteryState,
+    participant: &mut Participant,
+    owner: Pubkey,
+    rent_payer: Pubkey,
+    units: u64,
+) -> Result<()> {
+    if !participant.is_initialized {
+        participant.lottery_id = lottery_state.lottery_id;
+        participant.wallet = owner;
+        participant.rent_payer = rent_payer;
+        participant.tickets_count = 0;
+        participant.total_spent = 0;
+        participant.is_initialized = true;
+    }
+
+    if participant.tickets_count == 0 && units > 0 {
+        if let Some(max_participants) = lottery_state.max_participants {
+            require!(
+                lottery_state.participants_count < max_participants,
+                ErrorCode::MaxParticipantsReached
+            );
+        }
+        lottery_state.participants_count += 1;
+    }
+
+    let tickets_count = participant.tickets_count
+        .checked_add(units)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    if let Some(max_tickets_per_wallet) = lottery_state.max_tickets_per_wallet {
+        require!(
+            tickets_count <= max_tickets_per_wallet,
+            ErrorCode::MaxTicketsPerWalletReached
+        );
+    }
+    participant.tickets_count = tickets_count;
+    Ok(())
+}
+
+/// Takes ticket units back from a wallet, which stops counting as a participant once it holds none.
+pub fn remove_ticket_units(
+    lottery_state: &mut LotteryState,
+    participant: &mut Participant,
+    units: u64,
+) {
+    let had_tickets = participant.tickets_count > 0;
+    participant.tickets_count = participant.tickets_count.saturating_sub(units);
+    if had_tickets && participant.tickets_count == 0 {
+        lottery_state.participants_count = lottery_state.participants_count.saturating_sub(1);
+    }
+}
+
+/// Crank recording the slot sales closed on. It has to land before the randomness
+/// account is seeded, since commit requires a seed slot after sales_closed_slot.
+pub fn close_sales(ctx: Context<CloseLotterySales>, _lottery_id: u64) -> Result<()> {
+    msg!("Closing lottery sales");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    lottery_state.sync_status(&Clock::get()?)?;
+    require!(
+        lottery_state.status == LotteryStatus::SalesClosed,
+        ErrorCode::LotterySalesNotClosed
+    );
+    msg!(
+        "Lottery with id {} sales closed on slot {}",
+        lottery_state.lottery_id, lottery_state.sales_closed_slot
+    );
+    Ok(())
+}
+
+pub fn record_ticket_entry(
+    lottery_state: &mut LotteryState,
+    participant: &Pubkey,
+    combination: &String,
+    amount: u64,
+) {
+    lottery_state.ticket_set_hash = hashv(&[
+        &lottery_state.ticket_set_hash,
+        participant.as_ref(),
+        combination.as_bytes(),
+        &amount.to_le_bytes(),
+    ]).to_bytes();
+    lottery_state.ticket_set_entries += 1;
+    msg!(
+        "Ticket entry {} recorded, ticket set hash {:?}",
+        lottery_state.ticket_set_entries, lottery_state.ticket_set_hash
+    );
+}
+
+/// Appends a marketplace sale to the ticket set hash, so the set still says who holds
+/// each ticket at draw time. The tag and the second key keep it apart from purchases.
+pub fn record_ticket_transfer(
+    lottery_state: &mut LotteryState,
+    seller: &Pubkey,
+    buyer: &Pubkey,
+    combination: &String,
+    amount: u64,
+) {
+    lottery_state.ticket_set_hash = hashv(&[
+        &lottery_state.ticket_set_hash,
+        b"transfer",
+        seller.as_ref(),
+        buyer.as_ref(),
+        combination.as_bytes(),
+        &amount.to_le_bytes(),
+    ]).to_bytes();
+    lottery_state.ticket_set_entries += 1;
+    msg!(
+        "Ticket transfer {} recorded, ticket set hash {:?}",
+        lottery_state.ticket_set_entries, lottery_state.ticket_set_hash
+    );
+}
+
+pub fn finalize_tickets(ctx: Context<FinalizeLotteryTicketSet>, _lottery_id: u64) -> Result<()> {
+    msg!("Finalizing lottery ticket set");
+    let clock = Clock::get()?;
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    lottery_state.sync_status(&clock)?;
+
+    match lottery_state.status {
+        // Finalizing an open lottery freezes its sales right away
+        LotteryStatus::Open => lottery_state.close_sales(&clock)?,
+        LotteryStatus::SalesClosed => {}
+        LotteryStatus::Scheduled => return err!(ErrorCode::LotteryNotStarted),
+        LotteryStatus::Cancelled | LotteryStatus::Refunding => return err!(ErrorCode::LotteryCancelled),
+        _ => return err!(ErrorCode::TicketSetAlreadyFinalized),
+    }
+    require!(
+        !lottery_state.ticket_set_finalized,
+        ErrorCode::TicketSetAlreadyFinalized
+    );
+    lottery_state.ticket_set_finalized = true;
+
+    msg!(
+        "Lottery with id {} ticket set finalized with {} entries and hash {:?}",
+        lottery_state.lottery_id, lottery_state.ticket_set_entries, lottery_state.ticket_set_hash
+    );
+    Ok(())
+}
+
 pub fn commit_randomness(ctx: Context<CommitLotteryRandomness>, _lottery_id: u64) -> Result<()> {
     msg!("Committing randomness");
 
@@ -479,22 +1333,26 @@ pub fn commit_randomness(ctx: Context<CommitLotteryRandomness>, _lottery_id: u64
         ctx.accounts.randomness_account.data.borrow(),
     ).unwrap();
 
+    lottery_state.sync_status(&clock)?;
+    lottery_state.transition(LotteryStatus::RandomnessCommitted)?;
     require!(
-        lottery_state.lottery_end_datetime < clock.unix_timestamp, 
-        ErrorCode::CantCommitOnNotFinishedLottery
-    );
-    require!(
-        !lottery_state.is_closed, 
-        ErrorCode::LotteryClosed
+        lottery_state.ticket_set_finalized,
+        ErrorCode::TicketSetNotFinalized
     );
     require!(
-        lottery_state.winning_combination.is_none(), 
-        ErrorCode::CombinationAlreadySet
+        lottery_state.lottery_end_datetime < clock.unix_timestamp, 
+        ErrorCode::CantCommitOnNotFinishedLottery
     );
     require!(
         randomness_data.seed_slot == clock.slot - 1,
         ErrorCode::RandomnessAlreadyRevealed
     );
+    // Sales have to be closed on an earlier slot than the randomness seed,
+    // so no ticket can be bought knowing which slot seeds the draw.
+    require!(
+        randomness_data.seed_slot > lottery_state.sales_closed_slot,
+        ErrorCode::RandomnessSeededBeforeSalesClosed
+    );
 
     lottery_state.randomness_account = ctx.accounts.randomness_account.key();
     Ok(())
@@ -536,19 +1394,12 @@ pub fn reveal_randomness(ctx: Context<RevealLotteryRandomness>, _lottery_id: u64
     let clock = Clock::get()?;
     let lottery_state = &mut ctx.accounts.lottery_state;
 
+    lottery_state.sync_status(&clock)?;
+    lottery_state.transition(LotteryStatus::Drawn)?;
     require!(
         lottery_state.randomness_account == ctx.accounts.randomness_account.key(), 
         ErrorCode::IncorrectRandomnessAccount
     );
-    require!(
-        lottery_state.lottery_end_datetime < clock.unix_timestamp, 
-        ErrorCode::CantRevealOnNotFinishedLottery
-    );
-    require!(
-        lottery_state.winning_combination.is_none(), 
-        ErrorCode::CombinationAlreadySet
-    );
-    require!(!lottery_state.is_closed, ErrorCode::LotteryClosed);
 
     let randomness_data = 
         RandomnessAccountData::parse(ctx.accounts.randomness_account.data.borrow()).unwrap();
@@ -561,6 +1412,7 @@ pub fn reveal_randomness(ctx: Context<RevealLotteryRandomness>, _lottery_id: u64
         &revealed_random_value
     );
     lottery_state.winning_combination = Some(combination);
+    lottery_state.drawn_datetime = clock.unix_timestamp;
 
     Ok(())
 }
@@ -588,6 +1440,23 @@ pub fn calculate_num_tickets_reward(amount_payed: u64, ticket_price: u64, lotter
     return 1;
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Debug)]
+pub struct PrizeBreakdown {
+    pub full_matches: u8,
+    pub suit_matches: u8,
+    pub value_matches: u8,
+    // Length of every rewarded streak
+    pub suit_streaks: Vec<u8>,
+    pub value_streaks: Vec<u8>,
+    pub match_reward: f64,
+    pub streak_reward: f64,
+    pub jackpot_share: f64,
+    pub num_tickets_multiplier: u64,
+    pub prize: u64,
+    // Vault the prize would be paid from, the treasury when the lottery vault can't cover it
+    pub source_vault: Pubkey,
+}
+
 pub fn calculate_prize(
     winning_combination: &String, 
     combination: &String, 
@@ -597,10 +1466,11 @@ pub fn calculate_prize(
     accumulated_prize_pool: u64, 
     ticket_price: u64,
     reward_factors: &Account<'_, LotteryRewardFactors>
-) -> Result<u64> {
+) -> Result<PrizeBreakdown> {
     msg!("Calculating prize for combination {} given winning combination {} with lottery type {}", combination, winning_combination, lottery_type);
 
     let mut reward = 0.0;
+    let mut breakdown = PrizeBreakdown::default();
 
     let computed_ticket_price: f64 = if lottery_type == PAY_LOTTERY_TYPE {
         ticket_price as f64
@@ -626,11 +1496,20 @@ pub fn calculate_prize(
         let (winning_suit, winning_value) = winning_cards[i];
         
         if ticket_suit == winning_suit && ticket_value == winning_value {
-            reward += reward_factors.reward_full_match * computed_ticket_price * growth_factor;
+            let match_reward = reward_factors.reward_full_match * computed_ticket_price * growth_factor;
+            reward += match_reward;
+            breakdown.match_reward += match_reward;
+            breakdown.full_matches += 1;
         } else if ticket_suit == winning_suit {
-            reward += reward_factors.reward_suit_match * computed_ticket_price * growth_factor;
+            let match_reward = reward_factors.reward_suit_match * computed_ticket_price * growth_factor;
+            reward += match_reward;
+            breakdown.match_reward += match_reward;
+            breakdown.suit_matches += 1;
         } else if ticket_value == winning_value {
-            reward += reward_factors.reward_value_match * computed_ticket_price * growth_factor;
+            let match_reward = reward_factors.reward_value_match * computed_ticket_price * growth_factor;
+            reward += match_reward;
+            breakdown.match_reward += match_reward;
+            breakdown.value_matches += 1;
         }
         
         if i > 0 {
@@ -641,7 +1520,10 @@ pub fn calculate_prize(
                 suit_streak += 1;
             } else {
                 if suit_streak > 1 && suit_streak < reward_factors.suit_streak_bonuses.len() {
-                    reward += reward_factors.suit_streak_bonuses[suit_streak] * computed_ticket_price * growth_factor;
+                    let streak_bonus = reward_factors.suit_streak_bonuses[suit_streak] * computed_ticket_price * growth_factor;
+                    reward += streak_bonus;
+                    breakdown.streak_reward += streak_bonus;
+                    breakdown.suit_streaks.push(suit_streak as u8);
                 }
                 suit_streak = if ticket_suit == winning_suit { 1 } else { 0 };
             }
@@ -657,7 +1539,10 @@ pub fn calculate_prize(
                 value_streak += 1;
             } else {
                 if value_streak > 1 && value_streak < reward_factors.value_streak_bonuses.len() {
-                    reward += reward_factors.value_streak_bonuses[value_streak] * computed_ticket_price * growth_factor;
+                    let streak_bonus = reward_factors.value_streak_bonuses[value_streak] * computed_ticket_price * growth_factor;
+                    reward += streak_bonus;
+                    breakdown.streak_reward += streak_bonus;
+                    breakdown.value_streaks.push(value_streak as u8);
                 }
                 value_streak = if ticket_value == winning_value { 1 } else { 0 };
             }
@@ -667,10 +1552,16 @@ pub fn calculate_prize(
     }
     
     if suit_streak > 1 && suit_streak < reward_factors.suit_streak_bonuses.len() {
-        reward += reward_factors.suit_streak_bonuses[suit_streak] * computed_ticket_price * growth_factor;
+        let streak_bonus = reward_factors.suit_streak_bonuses[suit_streak] * computed_ticket_price * growth_factor;
+        reward += streak_bonus;
+        breakdown.streak_reward += streak_bonus;
+        breakdown.suit_streaks.push(suit_streak as u8);
     }
     if value_streak > 1 && value_streak < reward_factors.value_streak_bonuses.len() {
-        reward += reward_factors.value_streak_bonuses[value_streak] * computed_ticket_price * growth_factor;
+        let streak_bonus = reward_factors.value_streak_bonuses[value_streak] * computed_ticket_price * growth_factor;
+        reward += streak_bonus;
+        breakdown.streak_reward += streak_bonus;
+        breakdown.value_streaks.push(value_streak as u8);
     }
     
     let exact_match = (0..4).all(|i| {
@@ -680,7 +1571,8 @@ pub fn calculate_prize(
     });
     
     if exact_match {
-        reward += reward_factors.jackpot_percentage * accumulated_prize_pool as f64;
+        breakdown.jackpot_share = reward_factors.jackpot_percentage * accumulated_prize_pool as f64;
+        reward += breakdown.jackpot_share;
     }
     
     msg!("Calculated reward: {}", reward);
@@ -693,40 +1585,68 @@ pub fn calculate_prize(
         reward_factors.curvature
     );
 
-    msg!("Calculated num_tickets_reward: {}", num_tickets_reward);
-
-    Ok(if lottery_type == PAY_LOTTERY_TYPE {
-        ((reward.round()) as u64) * num_tickets_reward
-    } else {
-        amount_payed + (((reward.round()) as u64) * num_tickets_reward)
-    })
-}
-
-pub fn claim_prize_for_combination(
-    ctx: Context<ClaimLotteryPrizeForCombination>, 
-    _lottery_id: u64, 
-    _combination: String
-) -> Result<()> {
-    msg!("Claiming prize for combination");
-    let clock = Clock::get()?;
-    let lottery_state = &mut ctx.accounts.lottery_state;
-    let ticket = &mut ctx.accounts.ticket;
-    let accumulated_prize_pool = if lottery_state.lottery_type == PAY_LOTTERY_TYPE {
+    msg!("Calculated num_tickets_reward: {}", num_tickets_reward);
+
+    breakdown.num_tickets_multiplier = num_tickets_reward;
+    breakdown.prize = if lottery_type == PAY_LOTTERY_TYPE {
+        ((reward.round()) as u64) * num_tickets_reward
+    } else {
+        amount_payed + (((reward.round()) as u64) * num_tickets_reward)
+    };
+    Ok(breakdown)
+}
+
+// The pool prizes are computed against. LOCK tickets get their tokens back,
+// so only the treasury and sponsor funds make their pool grow.
+pub fn rewarded_prize_pool(lottery_state: &LotteryState) -> Result<u64> {
+    let prize_pool = if lottery_state.lottery_type == PAY_LOTTERY_TYPE {
         lottery_state.accumulated_prize_pool
     } else {
         lottery_state.initial_prize_pool
     };
+    Ok(prize_pool
+        .checked_add(lottery_state.sponsored_prize_pool)
+        .ok_or(ErrorCode::ArithmeticOverflow)?)
+}
 
+/// Prizes are paid from the lottery vault, or from the treasury when the vault can't cover them.
+/// Returns the seeds, bump and account of the PDA to withdraw from.
+pub fn prize_source<'a, 'info>(
+    scaled_prize: u64,
+    lottery_id_bytes: &'a [u8; 8],
+    lottery_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    lottery_token_account_bump: u8,
+    treasury_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    treasury_token_account_bump: u8,
+) -> (Vec<&'a [u8]>, u8, &'a InterfaceAccount<'info, TokenAccount>) {
+    if scaled_prize > lottery_token_account.amount {
+        (vec![b"treasury"], treasury_token_account_bump, treasury_token_account)
+    } else {
+        (
+            vec![b"lottery_token_account", lottery_id_bytes],
+            lottery_token_account_bump,
+            lottery_token_account,
+        )
+    }
+}
+
+/// Marks the ticket as claimed and returns its prize. Checking who is allowed to
+/// claim the ticket and paying the prize is left to the caller.
+pub fn settle_ticket_prize(
+    lottery_state: &mut LotteryState,
+    ticket: &mut Ticket,
+    lottery_reward_factors: &Account<LotteryRewardFactors>,
+) -> Result<u64> {
+    let accumulated_prize_pool = rewarded_prize_pool(lottery_state)?;
+    let clock = Clock::get()?;
     require!(
-        lottery_state.winning_combination.is_some(), 
-        ErrorCode::WinningCombinationNotSetYet
-    );
-    require!(
-        lottery_state.lottery_end_datetime < clock.unix_timestamp, 
-        ErrorCode::LotteryNotFinished
+        clock.unix_timestamp <= lottery_state.claim_deadline,
+        ErrorCode::ClaimWindowExpired
     );
+
+    lottery_state.sync_status(&clock)?;
+    lottery_state.transition(LotteryStatus::Claiming)?;
     require!(!ticket.is_claimed, ErrorCode::TicketAlreadyClaimed);
-    require!(!lottery_state.is_closed, ErrorCode::LotteryClosed);
 
     let prize = calculate_prize(
         lottery_state.winning_combination.as_ref().unwrap(),
@@ -736,8 +1656,39 @@ pub fn claim_prize_for_combination(
         lottery_state.initial_prize_pool,
         accumulated_prize_pool,
         lottery_state.min_tokens_per_participant,
-        &ctx.accounts.lottery_reward_factors
-    )?;
+        lottery_reward_factors
+    )?.prize;
+    ticket.is_claimed = true;
+    lottery_state.claimed_prize_amount = lottery_state.claimed_prize_amount
+        .checked_add(prize)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    Ok(prize)
+}
+
+pub fn claim_prize_for_combination(
+    ctx: Context<ClaimLotteryPrizeForCombination>, 
+    _lottery_id: u64, 
+    _combination: String
+) -> Result<()> {
+    msg!("Claiming prize for combination");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    let ticket = &mut ctx.accounts.ticket;
+
+    // In ticket NFT mode whoever holds the NFT owns the ticket
+    if lottery_state.ticket_nft_mode {
+        ticket_nft::enforce_ticket_nft_holder(
+            &ctx.accounts.ticket_mint,
+            &ctx.accounts.ticket_holder_account,
+            ctx.accounts.user.key(),
+        )?;
+    } else {
+        require!(
+            ticket.participant == ctx.accounts.user.key(),
+            ErrorCode::NotTicketOwner
+        );
+    }
+
+    let prize = settle_ticket_prize(lottery_state, ticket, &ctx.accounts.lottery_reward_factors)?;
 
     if prize > 0 {
         let lottery_id_bytes = lottery_state.lottery_id.to_le_bytes();
@@ -749,65 +1700,223 @@ pub fn claim_prize_for_combination(
         msg!("Scaled amount {}", scaled_amount);
         msg!("Lottery Token account amount {}", ctx.accounts.lottery_token_account.amount);
 
-        let not_enough_funds = scaled_amount > ctx.accounts.lottery_token_account.amount;
-        
-        let pda_seeds: &[&[u8]] = if not_enough_funds {
-            &[b"treasury"]
-        } else {
-            &[b"lottery_token_account", &lottery_id_bytes]
-        };
-        
-        let pda_bump: u8 = if not_enough_funds {
-            ctx.bumps.treasury_token_account
-        } else {
-            ctx.bumps.lottery_token_account
-        };
-        
-        let pda_owned_token_account = if not_enough_funds {
-            &ctx.accounts.treasury_token_account
-        } else {
-            &ctx.accounts.lottery_token_account
-        };
+        let (pda_seeds, pda_bump, pda_owned_token_account) = prize_source(
+            scaled_amount,
+            &lottery_id_bytes,
+            &ctx.accounts.lottery_token_account,
+            ctx.bumps.lottery_token_account,
+            &ctx.accounts.treasury_token_account,
+            ctx.bumps.treasury_token_account,
+        );
         
         let _ = pda_owned_token_accounts::withdraw(
             scaled_amount,
-            pda_seeds,
+            &pda_seeds,
             pda_bump,
             pda_owned_token_account,
-            &ctx.accounts.payer_token_account,
+            &ctx.accounts.user_token_account,
             &ctx.accounts.token_mint,
             &ctx.accounts.token_program,
         );
-        
-        
     }
-    ticket.is_claimed = true;
+    if let (Some(ticket_mint), Some(ticket_holder_account)) =
+        (&ctx.accounts.ticket_mint, &ctx.accounts.ticket_holder_account)
+    {
+        ticket_nft::burn_ticket_nft(
+            ticket_mint,
+            ticket_holder_account,
+            &ctx.accounts.user,
+            &ctx.accounts.token_program,
+        )?;
+    }
     Ok(())
 }
 
-pub fn close(ctx: Context<CloseLottery>, _lottery_id: u64) -> Result<()> {
-    msg!("Closing lottery");
+/// Computes the prize of a ticket without touching any account, meant to be simulated.
+/// The breakdown is sent back as Borsh encoded return data.
+pub fn preview(ctx: Context<PreviewPrize>, lottery_id: u64, _combination: String) -> Result<PrizeBreakdown> {
+    msg!("Previewing prize");
+    let lottery_state = &ctx.accounts.lottery_state;
+    let ticket = &ctx.accounts.ticket;
+    let winning_combination = lottery_state.winning_combination
+        .as_ref()
+        .ok_or(ErrorCode::WinningCombinationNotSetYet)?;
+
+    let mut breakdown = calculate_prize(
+        winning_combination,
+        &ticket.combination,
+        lottery_state.lottery_type,
+        ticket.amount,
+        lottery_state.initial_prize_pool,
+        rewarded_prize_pool(lottery_state)?,
+        lottery_state.min_tokens_per_participant,
+        &ctx.accounts.lottery_reward_factors
+    )?;
+
+    let lottery_id_bytes = lottery_id.to_le_bytes();
+    let scaled_prize = instructions::utils::get_scaled_amount(
+        breakdown.prize,
+        ctx.accounts.token_mint.decimals
+    )?;
+    let (_, _, source_vault) = prize_source(
+        scaled_prize,
+        &lottery_id_bytes,
+        &ctx.accounts.lottery_token_account,
+        ctx.bumps.lottery_token_account,
+        &ctx.accounts.treasury_token_account,
+        ctx.bumps.treasury_token_account,
+    );
+    breakdown.source_vault = source_vault.key();
+
+    Ok(breakdown)
+}
+
+pub fn claim_prizes<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ClaimLotteryPrizes<'info>>,
+    lottery_id: u64,
+    close_tickets: bool,
+) -> Result<()> {
+    msg!("Claiming prizes for {} tickets", ctx.remaining_accounts.len());
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    let payer = &ctx.accounts.payer;
+    // NFT tickets are burned on claim, which needs their mint and holder accounts
+    require!(!lottery_state.ticket_nft_mode, ErrorCode::TicketNftBatchClaim);
+
+    let lottery_id_bytes = lottery_id.to_le_bytes();
+    let mut total_prize: u64 = 0;
+    for ticket_info in ctx.remaining_accounts.iter() {
+        let mut ticket = Account::<Ticket>::try_from(ticket_info)?;
+        let (ticket_address, _) = Pubkey::find_program_address(
+            &[
+                b"ticket",
+                &lottery_id_bytes,
+                ticket.original_participant.as_ref(),
+                ticket.combination.as_bytes(),
+            ],
+            &crate::ID,
+        );
+        require_keys_eq!(ticket_info.key(), ticket_address, ErrorCode::NotTicketOwner);
+        require_keys_eq!(ticket.participant, payer.key(), ErrorCode::NotTicketOwner);
+
+        let prize = settle_ticket_prize(lottery_state, &mut ticket, &ctx.accounts.lottery_reward_factors)?;
+        total_prize = total_prize
+            .checked_add(prize)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        msg!("Ticket with combination {} won {} DRACO", ticket.combination, prize);
+
+        // Written back right away so a ticket passed twice is seen as already claimed.
+        // Only tickets whose rent the payer paid are closed here, the others go through
+        // close_lottery_ticket so the rent reaches their own payer.
+        if close_tickets && ticket.rent_payer == payer.key() {
+            ticket.close(payer.to_account_info())?;
+        } else {
+            ticket.exit(&crate::ID)?;
+        }
+    }
+
+    if total_prize > 0 {
+        let scaled_amount = instructions::utils::get_scaled_amount(
+            total_prize,
+            ctx.accounts.token_mint.decimals
+        )?;
+
+        let (pda_seeds, pda_bump, pda_owned_token_account) = prize_source(
+            scaled_amount,
+            &lottery_id_bytes,
+            &ctx.accounts.lottery_token_account,
+            ctx.bumps.lottery_token_account,
+            &ctx.accounts.treasury_token_account,
+            ctx.bumps.treasury_token_account,
+        );
+
+        pda_owned_token_accounts::withdraw(
+            scaled_amount,
+            &pda_seeds,
+            pda_bump,
+            pda_owned_token_account,
+            &ctx.accounts.payer_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+        )?;
+    }
+
+    msg!(
+        "Claimed {} DRACO for {} tickets of lottery_id {}",
+        total_prize, ctx.remaining_accounts.len(), lottery_id
+    );
+    Ok(())
+}
+
+pub fn expire_unclaimed(ctx: Context<ExpireUnclaimedPrizes>, _lottery_id: u64) -> Result<()> {
+    msg!("Expiring unclaimed prizes");
     let lottery_state = &mut ctx.accounts.lottery_state;
     let clock = Clock::get()?;
+    lottery_state.sync_status(&clock)?;
+    require!(
+        lottery_state.expired_unclaimed_amount.is_none(),
+        ErrorCode::UnclaimedPrizesAlreadyExpired
+    );
     require!(
-        lottery_state.winning_combination.is_some(),
+        matches!(lottery_state.status, LotteryStatus::Drawn | LotteryStatus::Claiming),
         ErrorCode::WinningCombinationNotSetYet
     );
     require!(
-        lottery_state.lottery_end_datetime < clock.unix_timestamp,
-        ErrorCode::LotteryNotFinished
+        lottery_state.claim_deadline < clock.unix_timestamp,
+        ErrorCode::ClaimWindowNotExpired
+    );
+
+    // Close sweeps the whole vault to the treasury, this is only the prize pool part of it
+    let expired_amount = unclaimed_prize_amount(lottery_state)?;
+    lottery_state.expired_unclaimed_amount = Some(expired_amount);
+
+    msg!(
+        "Lottery with id {} expired {} DRACO of unclaimed prizes",
+        lottery_state.lottery_id, expired_amount
+    );
+    Ok(())
+}
+
+/// Part of the prize pool never paid out to a claimed ticket.
+pub fn unclaimed_prize_amount(lottery_state: &LotteryState) -> Result<u64> {
+    Ok(rewarded_prize_pool(lottery_state)?.saturating_sub(lottery_state.claimed_prize_amount))
+}
+
+pub fn extend_claim_deadline(ctx: Context<ExtendLotteryClaimDeadline>, _lottery_id: u64, claim_deadline: i64) -> Result<()> {
+    msg!("Extending claim deadline");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    require!(
+        lottery_state.expired_unclaimed_amount.is_none(),
+        ErrorCode::UnclaimedPrizesAlreadyExpired
     );
     require!(
-        !lottery_state.is_closed, 
-        ErrorCode::LotteryClosed
+        claim_deadline > lottery_state.claim_deadline,
+        ErrorCode::InvalidClaimDeadline
     );
+    lottery_state.claim_deadline = claim_deadline;
+
+    msg!(
+        "Lottery with id {} claim deadline extended to {}",
+        lottery_state.lottery_id, claim_deadline
+    );
+    Ok(())
+}
+
+pub fn close(ctx: Context<CloseLottery>, _lottery_id: u64) -> Result<()> {
+    msg!("Closing lottery");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    let clock = Clock::get()?;
+    lottery_state.sync_status(&clock)?;
+    lottery_state.transition(LotteryStatus::Closed)?;
     require!(
-        lottery_state.lottery_end_datetime + LOTTERY_CLOSE_TIME_BUFFER < clock.unix_timestamp,
+        lottery_state.claim_deadline < clock.unix_timestamp,
         ErrorCode::LotteryNotReadyToBeClosed
     );
 
     let tokens_left = ctx.accounts.lottery_token_account.amount;
     let lottery_id_bytes = lottery_state.lottery_id.to_le_bytes();
+    if lottery_state.expired_unclaimed_amount.is_none() {
+        lottery_state.expired_unclaimed_amount = Some(unclaimed_prize_amount(lottery_state)?);
+    }
 
     let _ = pda_owned_token_accounts::withdraw(
         tokens_left,
@@ -819,7 +1928,272 @@ pub fn close(ctx: Context<CloseLottery>, _lottery_id: u64) -> Result<()> {
         &ctx.accounts.token_program,
     );
 
-    lottery_state.is_closed = true;
     msg!("Lottery with id {} closed successfully", lottery_state.lottery_id);
     Ok(())
-}
\ No newline at end of file
+}
+
+pub fn cancel(ctx: Context<CancelLottery>, _lottery_id: u64) -> Result<()> {
+    msg!("Cancelling lottery");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    lottery_state.sync_status(&Clock::get()?)?;
+    lottery_state.transition(LotteryStatus::Cancelled)?;
+
+    let lottery_id_bytes = lottery_state.lottery_id.to_le_bytes();
+    let scaled_amount = instructions::utils::get_scaled_amount(
+        lottery_state.initial_prize_pool,
+        ctx.accounts.token_mint.decimals
+    )?;
+
+    pda_owned_token_accounts::withdraw(
+        scaled_amount,
+        &[b"lottery_token_account", &lottery_id_bytes],
+        ctx.bumps.lottery_token_account,
+        &ctx.accounts.lottery_token_account,
+        &ctx.accounts.treasury_token_account,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+
+    lottery_state.accumulated_prize_pool = lottery_state.accumulated_prize_pool
+        .checked_sub(lottery_state.initial_prize_pool)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    msg!("Lottery with id {} cancelled successfully", lottery_state.lottery_id);
+    Ok(())
+}
+
+pub fn refund(ctx: Context<RefundTicket>, _lottery_id: u64, _combination: String) -> Result<()> {
+    msg!("Refunding ticket");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    let ticket = &ctx.accounts.ticket;
+    lottery_state.transition(LotteryStatus::Refunding)?;
+    if lottery_state.ticket_nft_mode {
+        ticket_nft::enforce_ticket_nft_holder(
+            &ctx.accounts.ticket_mint,
+            &ctx.accounts.ticket_holder_account,
+            ctx.accounts.payer.key(),
+        )?;
+        require!(
+            ctx.accounts.recipient.key() == ctx.accounts.payer.key(),
+            ErrorCode::NotTicketHolder
+        );
+    } else {
+        // Syndicate tickets belong to a program account without a wallet, see refund_ticket_for_syndicate
+        require!(
+            ctx.accounts.participant.owner != &crate::ID,
+            ErrorCode::TicketNotRefundable
+        );
+        require!(
+            ctx.accounts.recipient.key() == ticket.participant,
+            ErrorCode::NotTicketOwner
+        );
+    }
+
+    let lottery_id_bytes = lottery_state.lottery_id.to_le_bytes();
+    let scaled_amount = instructions::utils::get_scaled_amount(
+        ticket.amount,
+        ctx.accounts.token_mint.decimals
+    )?;
+
+    pda_owned_token_accounts::withdraw(
+        scaled_amount,
+        &[b"lottery_token_account", &lottery_id_bytes],
+        ctx.bumps.lottery_token_account,
+        &ctx.accounts.lottery_token_account,
+        &ctx.accounts.recipient_token_account,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+    if let (Some(ticket_mint), Some(ticket_holder_account)) =
+        (&ctx.accounts.ticket_mint, &ctx.accounts.ticket_holder_account)
+    {
+        ticket_nft::burn_ticket_nft(
+            ticket_mint,
+            ticket_holder_account,
+            &ctx.accounts.payer,
+            &ctx.accounts.token_program,
+        )?;
+    }
+
+    lottery_state.accumulated_prize_pool = lottery_state.accumulated_prize_pool
+        .checked_sub(ticket.amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    let participant_account = &mut ctx.accounts.participant_account;
+    let units = lottery_state.ticket_units(ticket.amount);
+    remove_ticket_units(lottery_state, participant_account, units);
+    participant_account.total_spent = participant_account.total_spent.saturating_sub(ticket.amount);
+    if participant_account.tickets_count == 0 {
+        participant_account.close(ctx.accounts.participant_rent_payer.to_account_info())?;
+    }
+
+    msg!(
+        "Refunded {} to {} for combination {} of lottery_id {}",
+        ticket.amount, ticket.participant, ticket.combination, ticket.lottery_id
+    );
+    Ok(())
+}
+
+pub fn update_scheduled(
+    ctx: Context<UpdateScheduledLottery>,
+    _lottery_id: u64,
+    lottery_name: Option<String>,
+    lottery_description: Option<String>,
+    lottery_start_datetime: Option<i64>,
+    lottery_end_datetime: Option<i64>,
+    min_tokens_per_participant: Option<u64>,
+) -> Result<()> {
+    msg!("Updating lottery");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    lottery_state.sync_status(&Clock::get()?)?;
+
+    // Before the lottery opens everything can be edited. Once it opens, and only
+    // while no ticket has been sold, the description can change and the end can be pushed back.
+    match lottery_state.status {
+        LotteryStatus::Scheduled => {}
+        LotteryStatus::Open => {
+            require!(
+                lottery_state.participants_count == 0,
+                ErrorCode::LotteryTicketsAlreadySold
+            );
+            require!(
+                lottery_name.is_none()
+                    && lottery_start_datetime.is_none()
+                    && min_tokens_per_participant.is_none(),
+                ErrorCode::LotteryAlreadyStarted
+            );
+            if let Some(end_datetime) = lottery_end_datetime {
+                require!(
+                    end_datetime > lottery_state.lottery_end_datetime,
+                    ErrorCode::CantShortenStartedLottery
+                );
+            }
+        }
+        _ => return lottery_state.require_open(),
+    }
+
+    let start_datetime = lottery_start_datetime.unwrap_or(lottery_state.lottery_start_datetime);
+    let end_datetime = lottery_end_datetime.unwrap_or(lottery_state.lottery_end_datetime);
+    require!(
+        start_datetime < end_datetime,
+        ErrorCode::InvalidLotteryStartEndDatetime
+    );
+    require!(
+        start_datetime < end_datetime.saturating_sub(lottery_state.sales_close_buffer),
+        ErrorCode::InvalidSalesCloseBuffer
+    );
+    if let Some(min_tokens) = min_tokens_per_participant {
+        require!(
+            min_tokens > 0,
+            ErrorCode::InvalidMinTokensPerParticipant
+        );
+        lottery_state.min_tokens_per_participant = min_tokens;
+    }
+    if let Some(name) = lottery_name {
+        lottery_state.lottery_name = name;
+    }
+    if let Some(description) = lottery_description {
+        lottery_state.lottery_description = description;
+    }
+    lottery_state.lottery_start_datetime = start_datetime;
+    lottery_state.lottery_end_datetime = end_datetime;
+    let min_claim_deadline = end_datetime
+        .checked_add(LOTTERY_CLOSE_TIME_BUFFER)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    lottery_state.claim_deadline = lottery_state.claim_deadline.max(min_claim_deadline);
+    lottery_state.sync_status(&Clock::get()?)?;
+
+    msg!("Lottery with id {} updated successfully", lottery_state.lottery_id);
+    Ok(())
+}
+
+pub fn top_up_prize_pool(ctx: Context<TopUpLotteryPrizePool>, _lottery_id: u64, amount: u64) -> Result<()> {
+    msg!("Topping up lottery prize pool with {} DRACO", amount);
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    lottery_state.sync_status(&Clock::get()?)?;
+    require!(
+        matches!(lottery_state.status, LotteryStatus::Scheduled | LotteryStatus::Open),
+        ErrorCode::LotteryFinished
+    );
+    require!(amount > 0, ErrorCode::InvalidAmount);
+
+    // Top ups come from the treasury just like the initial pool, so they count
+    // as part of it and don't inflate the growth factor.
+    lottery_state.initial_prize_pool = lottery_state.initial_prize_pool
+        .checked_add(amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    lottery_state.accumulated_prize_pool = lottery_state.accumulated_prize_pool
+        .checked_add(amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    let scaled_amount = instructions::utils::get_scaled_amount(
+        amount,
+        ctx.accounts.token_mint.decimals
+    )?;
+
+    pda_owned_token_accounts::withdraw(
+        scaled_amount,
+        &[b"treasury"],
+        ctx.bumps.treasury_token_account,
+        &ctx.accounts.treasury_token_account,
+        &ctx.accounts.lottery_token_account,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+
+    msg!("Lottery with id {} prize pool topped up successfully", lottery_state.lottery_id);
+    Ok(())
+}
+
+pub fn close_ticket(ctx: Context<CloseLotteryTicket>, _lottery_id: u64, _combination: String) -> Result<()> {
+    msg!("Closing ticket");
+    let ticket = &ctx.accounts.ticket;
+
+    // Unclaimed tickets can't be claimed anymore once the lottery is closed,
+    // and the tombstone is left behind when the lottery accounts are closed.
+    let lottery_state_data = ctx.accounts.lottery_state.try_borrow_data()?;
+    let lottery_closed = !ctx.accounts.lottery_tombstone.data_is_empty()
+        || (!lottery_state_data.is_empty()
+            && LotteryState::try_deserialize(&mut &lottery_state_data[..])?.status == LotteryStatus::Closed);
+    require!(
+        ticket.is_claimed || lottery_closed,
+        ErrorCode::TicketNotClosable
+    );
+
+    msg!(
+        "Ticket with combination {} of lottery_id {} closed, rent returned to {}",
+        ticket.combination, ticket.lottery_id, ticket.rent_payer
+    );
+    Ok(())
+}
+
+pub fn close_accounts(ctx: Context<CloseLotteryAccounts>, _lottery_id: u64) -> Result<()> {
+    msg!("Closing lottery accounts");
+    let lottery_state = &ctx.accounts.lottery_state;
+    require!(
+        matches!(
+            lottery_state.status,
+            LotteryStatus::Closed | LotteryStatus::Cancelled | LotteryStatus::Refunding
+        ),
+        ErrorCode::LotteryNotClosed
+    );
+    require!(
+        ctx.accounts.lottery_token_account.amount == 0,
+        ErrorCode::LotteryVaultNotEmpty
+    );
+
+    let lottery_id_bytes = lottery_state.lottery_id.to_le_bytes();
+    pda_owned_token_accounts::close_token_account(
+        &[b"lottery_token_account", &lottery_id_bytes],
+        ctx.bumps.lottery_token_account,
+        &ctx.accounts.lottery_token_account,
+        ctx.accounts.payer.to_account_info(),
+        &ctx.accounts.token_program,
+    )?;
+
+    let lottery_tombstone = &mut ctx.accounts.lottery_tombstone;
+    lottery_tombstone.lottery_id = lottery_state.lottery_id;
+    lottery_tombstone.closed_at = Clock::get()?.unix_timestamp;
+
+    msg!("Lottery with id {} accounts closed successfully", lottery_state.lottery_id);
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{ Mint, TokenInterface, TokenAccount}};
+
+use crate::instructions;
+use crate::state::lottery::{LotteryState, LotteryStatus};
+use crate::state::sponsorship::Sponsorship;
+use crate::state::protocol_error::ErrorCode;
+
+use crate::instructions::pda_owned_token_accounts;
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct SponsorLottery<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_token_account".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [
+            b"sponsorship".as_ref(), 
+            lottery_id.to_le_bytes().as_ref(), 
+            payer.key().to_bytes().as_ref(),
+        ],
+        bump,
+        space = 8 + Sponsorship::INIT_SPACE,
+    )]
+    pub sponsorship: Account<'info, Sponsorship>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64)]
+pub struct RefundLotterySponsorship<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Only receives the refund and the sponsorship rent, it must match the sponsor.
+    #[account(
+        mut,
+        address = sponsorship.sponsor
+    )]
+    pub sponsor: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = sponsor,
+        associated_token::token_program = token_program
+    )]
+    pub sponsor_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_token_account".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(), 
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(
+        mut,
+        close = sponsor,
+        seeds = [
+            b"sponsorship".as_ref(), 
+            lottery_id.to_le_bytes().as_ref(), 
+            sponsorship.sponsor.to_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub sponsorship: Account<'info, Sponsorship>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+pub fn sponsor(ctx: Context<SponsorLottery>, lottery_id: u64, amount: u64) -> Result<()> {
+    msg!("Sponsoring lottery with {} DRACO", amount);
+    require!(amount > 0, ErrorCode::InvalidAmount);
+
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    lottery_state.sync_status(&Clock::get()?)?;
+    // Sponsoring changes every prize, so it has to happen before the randomness is committed
+    require!(
+        matches!(
+            lottery_state.status,
+            LotteryStatus::Scheduled | LotteryStatus::Open | LotteryStatus::SalesClosed
+        ),
+        ErrorCode::LotterySponsorshipClosed
+    );
+
+    lottery_state.sponsored_prize_pool = lottery_state.sponsored_prize_pool
+        .checked_add(amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    let sponsorship = &mut ctx.accounts.sponsorship;
+    sponsorship.lottery_id = lottery_id;
+    sponsorship.sponsor = ctx.accounts.payer.key();
+    sponsorship.amount = sponsorship.amount
+        .checked_add(amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    let scaled_amount = instructions::utils::get_scaled_amount(
+        amount,
+        ctx.accounts.token_mint.decimals
+    )?;
+
+    pda_owned_token_accounts::contribute(
+        scaled_amount,
+        &ctx.accounts.payer_token_account,
+        &ctx.accounts.lottery_token_account,
+        &ctx.accounts.payer,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+
+    msg!(
+        "Lottery with id {} sponsored by {} with {} DRACO successfully",
+        lottery_id, sponsorship.sponsor, sponsorship.amount
+    );
+    Ok(())
+}
+
+pub fn refund_sponsor(ctx: Context<RefundLotterySponsorship>, _lottery_id: u64) -> Result<()> {
+    msg!("Refunding lottery sponsorship");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    let sponsorship = &ctx.accounts.sponsorship;
+    lottery_state.transition(LotteryStatus::Refunding)?;
+
+    let lottery_id_bytes = lottery_state.lottery_id.to_le_bytes();
+    let scaled_amount = instructions::utils::get_scaled_amount(
+        sponsorship.amount,
+        ctx.accounts.token_mint.decimals
+    )?;
+
+    pda_owned_token_accounts::withdraw(
+        scaled_amount,
+        &[b"lottery_token_account", &lottery_id_bytes],
+        ctx.bumps.lottery_token_account,
+        &ctx.accounts.lottery_token_account,
+        &ctx.accounts.sponsor_token_account,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+
+    lottery_state.sponsored_prize_pool = lottery_state.sponsored_prize_pool
+        .checked_sub(sponsorship.amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    msg!(
+        "Refunded {} DRACO to sponsor {} of lottery_id {}",
+        sponsorship.amount, sponsorship.sponsor, sponsorship.lottery_id
+    );
+    Ok(())
+}
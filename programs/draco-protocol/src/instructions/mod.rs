@@ -13,15 +13,30 @@ pub use initialize_treasury::*;
 pub mod reward_factors;
 pub use reward_factors::*;
 
-pub mod six_month_cliff;
-pub use six_month_cliff::*;
 
 pub mod lottery;
 pub use lottery::*;
 
+pub mod ticket_nft;
+pub use ticket_nft::*;
+
+pub mod lottery_sponsorship;
+pub use lottery_sponsorship::*;
+
+pub mod ticket_marketplace;
+pub use ticket_marketplace::*;
+
+pub mod syndicate;
+pub use syndicate::*;
+
+pub mod payout;
+pub use payout::*;
+
 pub mod utils;
 pub use utils::*;
 
 pub mod airdrop;
 pub use airdrop::*;
 
+pub mod vesting_schedule;
+pub use vesting_schedule::*;
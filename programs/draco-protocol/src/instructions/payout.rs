@@ -0,0 +1,218 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{ Mint, TokenInterface, TokenAccount}};
+
+use crate::{instructions, state::protocol_authority::ProtocolAuthority};
+use crate::state::lottery::LotteryState;
+use crate::state::lottery_reward_factors::LotteryRewardFactors;
+use crate::state::payout_config::PayoutConfig;
+use crate::state::ticket::Ticket;
+use crate::state::protocol_error::ErrorCode;
+
+use crate::instructions::{lottery, pda_owned_token_accounts};
+
+use crate::constants::{LOCK_LOTTERY_TYPE, MAX_PAYOUT_BOUNTY, PAYOUT_BOUNTY_GRACE_PERIOD};
+
+#[derive(Accounts)]
+pub struct SetPayoutBounty<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [b"payout_config".as_ref()],
+        bump,
+        space = 8 + PayoutConfig::INIT_SPACE,
+    )]
+    pub payout_config: Account<'info, PayoutConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, combination: String)]
+pub struct PayoutTicket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Only receives the prize, it must match the ticket participant.
+    #[account(
+        address = ticket.participant
+    )]
+    pub owner: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program
+    )]
+    pub owner_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_token_account".as_ref(),
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"ticket".as_ref(),
+            lottery_id.to_le_bytes().as_ref(),
+            ticket.original_participant.to_bytes().as_ref(),
+            combination.as_ref()
+            ],
+        bump,
+    )]
+    pub ticket: Account<'info, Ticket>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(),
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(
+        seeds = [b"lottery_reward_factors".as_ref()],
+        bump
+    )]
+    pub lottery_reward_factors: Account<'info, LotteryRewardFactors>,
+
+    #[account(
+        seeds = [b"payout_config".as_ref()],
+        bump
+    )]
+    pub payout_config: Account<'info, PayoutConfig>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+pub fn set_bounty(ctx: Context<SetPayoutBounty>, payout_bounty: u64) -> Result<()> {
+    msg!("Setting payout bounty to {} DRACO", payout_bounty);
+    require!(payout_bounty <= MAX_PAYOUT_BOUNTY, ErrorCode::InvalidPayoutBounty);
+    ctx.accounts.payout_config.payout_bounty = payout_bounty;
+    Ok(())
+}
+
+pub fn pay_out(ctx: Context<PayoutTicket>, _lottery_id: u64, _combination: String) -> Result<()> {
+    msg!("Paying out ticket");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    let ticket = &mut ctx.accounts.ticket;
+
+    // NFT tickets belong to whoever holds the NFT and syndicate tickets to a program
+    // account without a wallet, both have to go through their own claim.
+    require!(
+        !lottery_state.ticket_nft_mode && ctx.accounts.owner.owner != &crate::ID,
+        ErrorCode::TicketNotPayable
+    );
+
+    let prize = lottery::settle_ticket_prize(lottery_state, ticket, &ctx.accounts.lottery_reward_factors)?;
+    // Losing tickets are still marked as claimed, but only winning ones earn the bounty
+    if prize == 0 {
+        msg!("Ticket with combination {} didn't win a prize", ticket.combination);
+        return Ok(());
+    }
+
+    let lottery_id_bytes = lottery_state.lottery_id.to_le_bytes();
+    let scaled_prize = instructions::utils::get_scaled_amount(
+        prize,
+        ctx.accounts.token_mint.decimals
+    )?;
+
+    let (pda_seeds, pda_bump, pda_owned_token_account) = lottery::prize_source(
+        scaled_prize,
+        &lottery_id_bytes,
+        &ctx.accounts.lottery_token_account,
+        ctx.bumps.lottery_token_account,
+        &ctx.accounts.treasury_token_account,
+        ctx.bumps.treasury_token_account,
+    );
+
+    pda_owned_token_accounts::withdraw(
+        scaled_prize,
+        &pda_seeds,
+        pda_bump,
+        pda_owned_token_account,
+        &ctx.accounts.owner_token_account,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+
+    // LOCK prizes give the locked principal back, only what the ticket won on top of it counts
+    let reward = if lottery_state.lottery_type == LOCK_LOTTERY_TYPE {
+        prize.saturating_sub(ticket.amount)
+    } else {
+        prize
+    };
+    // Owners cranking their own ticket, or anyone cranking before owners had the grace period
+    // to claim themselves, get no bounty. Never more than the reward, so small prizes can't
+    // drain the treasury.
+    let bounty_open = Clock::get()?.unix_timestamp
+        >= lottery_state.drawn_datetime.saturating_add(PAYOUT_BOUNTY_GRACE_PERIOD);
+    let payout_bounty = if ctx.accounts.payer.key() == ticket.participant || !bounty_open {
+        0
+    } else {
+        ctx.accounts.payout_config.payout_bounty.min(reward)
+    };
+    if payout_bounty > 0 {
+        let scaled_bounty = instructions::utils::get_scaled_amount(
+            payout_bounty,
+            ctx.accounts.token_mint.decimals
+        )?;
+
+        pda_owned_token_accounts::withdraw(
+            scaled_bounty,
+            &[b"treasury"],
+            ctx.bumps.treasury_token_account,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.payer_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+        )?;
+    }
+
+    msg!(
+        "Paid {} DRACO to {} for combination {}, {} DRACO bounty to {}",
+        prize, ticket.participant, ticket.combination, payout_bounty, ctx.accounts.payer.key()
+    );
+    Ok(())
+}
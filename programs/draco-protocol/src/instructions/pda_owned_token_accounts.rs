@@ -1,6 +1,6 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{
-    transfer_checked, Mint, TokenInterface, TokenAccount, TransferChecked
+    close_account, transfer_checked, CloseAccount, Mint, TokenInterface, TokenAccount, TransferChecked
 };
 
 pub fn contribute<'info>(
@@ -61,4 +61,30 @@ pub fn withdraw<'info>(
     )?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+pub fn close_token_account<'info>(
+    pda_seeds: &[&[u8]],
+    pda_bump: u8,
+    pda_owned_token_account: &InterfaceAccount<'info, TokenAccount>,
+    destination: AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let bump_slice = [pda_bump];
+    let mut seeds_with_bump = pda_seeds.to_vec();
+    seeds_with_bump.push(&bump_slice);
+    let signer_seeds: &[&[&[u8]]] = &[&seeds_with_bump];
+
+    let cpi_accounts = CloseAccount{
+        account: pda_owned_token_account.to_account_info(),
+        destination,
+        authority: pda_owned_token_account.to_account_info(),
+    };
+
+    let cpi_program = token_program.to_account_info();
+    let cpi_context = CpiContext::new(cpi_program, cpi_accounts).with_signer(signer_seeds);
+
+    close_account(cpi_context)?;
+
+    Ok(())
+}
@@ -0,0 +1,688 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{ Mint, TokenInterface, TokenAccount}};
+
+use crate::instructions;
+use crate::state::lottery::{LotteryState, LotteryStatus};
+use crate::state::lottery_reward_factors::LotteryRewardFactors;
+use crate::state::participant::Participant;
+use crate::state::syndicate::{Syndicate, SyndicateMember};
+use crate::state::ticket::Ticket;
+use crate::state::protocol_error::ErrorCode;
+
+use crate::instructions::{lottery, pda_owned_token_accounts};
+
+#[derive(Accounts)]
+#[instruction(syndicate_id: u64, lottery_id: u64)]
+pub struct CreateSyndicate<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"syndicate".as_ref(),
+            syndicate_id.to_le_bytes().as_ref()
+        ],
+        bump,
+        space = 8 + Syndicate::INIT_SPACE,
+    )]
+    pub syndicate: Account<'info, Syndicate>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"syndicate_token_account".as_ref(),
+            syndicate_id.to_le_bytes().as_ref()
+        ],
+        bump,
+        token::mint = token_mint,
+        token::authority = syndicate_token_account,
+    )]
+    pub syndicate_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(),
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(syndicate_id: u64)]
+pub struct ContributeToSyndicate<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"syndicate".as_ref(),
+            syndicate_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub syndicate: Account<'info, Syndicate>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"syndicate_token_account".as_ref(),
+            syndicate_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub syndicate_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [
+            b"syndicate_member".as_ref(),
+            syndicate_id.to_le_bytes().as_ref(),
+            payer.key().to_bytes().as_ref(),
+        ],
+        bump,
+        space = 8 + SyndicateMember::INIT_SPACE,
+    )]
+    pub syndicate_member: Account<'info, SyndicateMember>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(syndicate_id: u64, combination: String)]
+pub struct BuySyndicateTicket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [
+            b"syndicate".as_ref(),
+            syndicate_id.to_le_bytes().as_ref()
+        ],
+        bump,
+        constraint = syndicate.manager == payer.key() @ ErrorCode::NotSyndicateManager,
+    )]
+    pub syndicate: Account<'info, Syndicate>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"syndicate_token_account".as_ref(),
+            syndicate_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub syndicate_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_token_account".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [
+            b"ticket".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref(),
+            syndicate.key().to_bytes().as_ref(),
+            combination.as_ref()
+            ],
+        bump,
+        space = 8 + Ticket::INIT_SPACE,
+    )]
+    pub ticket: Account<'info, Ticket>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [
+            b"participant".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref(),
+            syndicate.key().to_bytes().as_ref(),
+            ],
+        bump,
+        space = 8 + Participant::INIT_SPACE,
+    )]
+    pub participant: Account<'info, Participant>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(syndicate_id: u64, combination: String)]
+pub struct ClaimSyndicatePrize<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [
+            b"syndicate".as_ref(),
+            syndicate_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub syndicate: Account<'info, Syndicate>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"syndicate_token_account".as_ref(),
+            syndicate_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub syndicate_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_token_account".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"ticket".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref(),
+            ticket.original_participant.to_bytes().as_ref(),
+            combination.as_ref()
+            ],
+        bump,
+        constraint = ticket.participant == syndicate.key() @ ErrorCode::NotTicketOwner,
+    )]
+    pub ticket: Account<'info, Ticket>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(
+        seeds = [b"lottery_reward_factors".as_ref()],
+        bump
+    )]
+    pub lottery_reward_factors: Account<'info, LotteryRewardFactors>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(syndicate_id: u64, combination: String)]
+pub struct RefundSyndicateTicket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [
+            b"syndicate".as_ref(),
+            syndicate_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub syndicate: Account<'info, Syndicate>,
+
+    /// CHECK: Only receives the ticket and participant rent, it must match the syndicate manager who paid it.
+    #[account(
+        mut,
+        address = syndicate.manager
+    )]
+    pub manager: UncheckedAccount<'info>,
+
+    // Refunds go back to the pool, members get them through withdraw_syndicate_share
+    #[account(
+        mut,
+        seeds = [
+            b"syndicate_token_account".as_ref(),
+            syndicate_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub syndicate_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_token_account".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        close = manager,
+        seeds = [
+            b"ticket".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref(),
+            ticket.original_participant.to_bytes().as_ref(),
+            combination.as_ref()
+            ],
+        bump,
+        constraint = ticket.participant == syndicate.key() @ ErrorCode::NotTicketOwner,
+    )]
+    pub ticket: Account<'info, Ticket>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"participant".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref(),
+            syndicate.key().to_bytes().as_ref(),
+            ],
+        bump,
+    )]
+    pub participant: Account<'info, Participant>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(syndicate_id: u64)]
+pub struct WithdrawSyndicateShare<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"syndicate".as_ref(),
+            syndicate_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub syndicate: Account<'info, Syndicate>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"syndicate_token_account".as_ref(),
+            syndicate_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub syndicate_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"syndicate_member".as_ref(),
+            syndicate_id.to_le_bytes().as_ref(),
+            payer.key().to_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub syndicate_member: Account<'info, SyndicateMember>,
+
+    /// CHECK: The lottery state may already be closed, it's deserialized in the handler when it still exists.
+    #[account(
+        seeds = [
+            b"lottery_state".as_ref(),
+            syndicate.lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+pub fn new_syndicate(ctx: Context<CreateSyndicate>, syndicate_id: u64, lottery_id: u64) -> Result<()> {
+    msg!("Creating syndicate");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    lottery_state.sync_status(&Clock::get()?)?;
+    require!(
+        matches!(lottery_state.status, LotteryStatus::Scheduled | LotteryStatus::Open),
+        ErrorCode::SyndicateContributionsClosed
+    );
+    // NFT tickets need a wallet to hold them
+    require!(!lottery_state.ticket_nft_mode, ErrorCode::SyndicateNftModeNotSupported);
+
+    let syndicate = &mut ctx.accounts.syndicate;
+    syndicate.syndicate_id = syndicate_id;
+    syndicate.lottery_id = lottery_id;
+    syndicate.manager = ctx.accounts.payer.key();
+    syndicate.members_count = 0;
+    syndicate.total_contributed = 0;
+    syndicate.total_withdrawn = 0;
+
+    msg!(
+        "Syndicate {} for lottery_id {} created by {}",
+        syndicate_id, lottery_id, syndicate.manager
+    );
+    Ok(())
+}
+
+pub fn add_contribution(ctx: Context<ContributeToSyndicate>, syndicate_id: u64, amount: u64) -> Result<()> {
+    msg!("Contributing {} DRACO to syndicate", amount);
+    require!(amount > 0, ErrorCode::InvalidAmount);
+
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    lottery_state.sync_status(&Clock::get()?)?;
+    require!(
+        matches!(lottery_state.status, LotteryStatus::Scheduled | LotteryStatus::Open),
+        ErrorCode::SyndicateContributionsClosed
+    );
+
+    let syndicate = &mut ctx.accounts.syndicate;
+    let syndicate_member = &mut ctx.accounts.syndicate_member;
+    if !syndicate_member.is_initialized {
+        syndicate_member.syndicate_id = syndicate_id;
+        syndicate_member.member = ctx.accounts.payer.key();
+        syndicate_member.contributed = 0;
+        syndicate_member.withdrawn = 0;
+        syndicate_member.is_initialized = true;
+        syndicate.members_count += 1;
+    }
+    syndicate_member.contributed = syndicate_member.contributed
+        .checked_add(amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    syndicate.total_contributed = syndicate.total_contributed
+        .checked_add(amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    let scaled_amount = instructions::utils::get_scaled_amount(
+        amount,
+        ctx.accounts.token_mint.decimals
+    )?;
+
+    pda_owned_token_accounts::contribute(
+        scaled_amount,
+        &ctx.accounts.payer_token_account,
+        &ctx.accounts.syndicate_token_account,
+        &ctx.accounts.payer,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+
+    msg!(
+        "Member {} contributed {} DRACO to syndicate {}",
+        syndicate_member.member, syndicate_member.contributed, syndicate_id
+    );
+    Ok(())
+}
+
+pub fn buy_ticket_for_syndicate(
+    ctx: Context<BuySyndicateTicket>,
+    syndicate_id: u64,
+    combination: String,
+    amount: u64,
+) -> Result<()> {
+    msg!("Buying ticket for syndicate");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    lottery_state.sync_status(&Clock::get()?)?;
+    lottery_state.require_open()?;
+    lottery::verify_combination(&combination)?;
+    lottery::verify_amount_on_type(lottery_state, amount)?;
+
+    let syndicate = &ctx.accounts.syndicate;
+    let ticket = &mut ctx.accounts.ticket;
+    lottery::register_ticket(
+        lottery_state,
+        &mut ctx.accounts.participant,
+        ticket,
+        syndicate.key(),
+        ctx.accounts.payer.key(),
+        amount,
+        combination,
+    )?;
+
+    let syndicate_id_bytes = syndicate_id.to_le_bytes();
+    let scaled_amount = instructions::utils::get_scaled_amount(
+        amount,
+        ctx.accounts.token_mint.decimals
+    )?;
+
+    pda_owned_token_accounts::withdraw(
+        scaled_amount,
+        &[b"syndicate_token_account", &syndicate_id_bytes],
+        ctx.bumps.syndicate_token_account,
+        &ctx.accounts.syndicate_token_account,
+        &ctx.accounts.lottery_token_account,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+
+    msg!(
+        "Syndicate {} bought ticket from lottery_id {} with amount {} and combination {}",
+        syndicate_id, ticket.lottery_id, ticket.amount, ticket.combination
+    );
+    Ok(())
+}
+
+pub fn claim_prize_for_syndicate(ctx: Context<ClaimSyndicatePrize>, syndicate_id: u64, _combination: String) -> Result<()> {
+    msg!("Claiming prize for syndicate");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    let ticket = &mut ctx.accounts.ticket;
+    let prize = lottery::settle_ticket_prize(lottery_state, ticket, &ctx.accounts.lottery_reward_factors)?;
+
+    if prize > 0 {
+        let lottery_id_bytes = lottery_state.lottery_id.to_le_bytes();
+        let scaled_amount = instructions::utils::get_scaled_amount(
+            prize,
+            ctx.accounts.token_mint.decimals
+        )?;
+
+        let (pda_seeds, pda_bump, pda_owned_token_account) = lottery::prize_source(
+            scaled_amount,
+            &lottery_id_bytes,
+            &ctx.accounts.lottery_token_account,
+            ctx.bumps.lottery_token_account,
+            &ctx.accounts.treasury_token_account,
+            ctx.bumps.treasury_token_account,
+        );
+
+        pda_owned_token_accounts::withdraw(
+            scaled_amount,
+            &pda_seeds,
+            pda_bump,
+            pda_owned_token_account,
+            &ctx.accounts.syndicate_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+        )?;
+    }
+
+    msg!(
+        "Syndicate {} claimed {} DRACO for combination {}",
+        syndicate_id, prize, ticket.combination
+    );
+    Ok(())
+}
+
+pub fn refund_ticket_for_syndicate(ctx: Context<RefundSyndicateTicket>, syndicate_id: u64, _combination: String) -> Result<()> {
+    msg!("Refunding syndicate ticket");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    let ticket = &ctx.accounts.ticket;
+    lottery_state.transition(LotteryStatus::Refunding)?;
+
+    let lottery_id_bytes = lottery_state.lottery_id.to_le_bytes();
+    let scaled_amount = instructions::utils::get_scaled_amount(
+        ticket.amount,
+        ctx.accounts.token_mint.decimals
+    )?;
+
+    pda_owned_token_accounts::withdraw(
+        scaled_amount,
+        &[b"lottery_token_account", &lottery_id_bytes],
+        ctx.bumps.lottery_token_account,
+        &ctx.accounts.lottery_token_account,
+        &ctx.accounts.syndicate_token_account,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+
+    lottery_state.accumulated_prize_pool = lottery_state.accumulated_prize_pool
+        .checked_sub(ticket.amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    let participant = &mut ctx.accounts.participant;
+    let units = lottery_state.ticket_units(ticket.amount);
+    lottery::remove_ticket_units(lottery_state, participant, units);
+    participant.total_spent = participant.total_spent.saturating_sub(ticket.amount);
+    if participant.tickets_count == 0 {
+        participant.close(ctx.accounts.manager.to_account_info())?;
+    }
+
+    msg!(
+        "Refunded {} to syndicate {} for combination {} of lottery_id {}",
+        ticket.amount, syndicate_id, ticket.combination, ticket.lottery_id
+    );
+    Ok(())
+}
+
+pub fn withdraw_share(ctx: Context<WithdrawSyndicateShare>, syndicate_id: u64) -> Result<()> {
+    msg!("Withdrawing syndicate share");
+
+    // Shares are only final once no more tickets can be bought, and if the
+    // lottery state is gone the lottery was closed long ago.
+    let lottery_state_data = ctx.accounts.lottery_state.try_borrow_data()?;
+    let withdrawals_open = lottery_state_data.is_empty()
+        || matches!(
+            LotteryState::try_deserialize(&mut &lottery_state_data[..])?.status,
+            LotteryStatus::Drawn
+                | LotteryStatus::Claiming
+                | LotteryStatus::Closed
+                | LotteryStatus::Cancelled
+                | LotteryStatus::Refunding
+        );
+    require!(withdrawals_open, ErrorCode::SyndicateWithdrawalsNotOpen);
+
+    // Members get their share of everything the vault ever held: unspent
+    // contributions plus every prize claimed so far.
+    let syndicate = &mut ctx.accounts.syndicate;
+    let syndicate_member = &mut ctx.accounts.syndicate_member;
+    let vault_total = (ctx.accounts.syndicate_token_account.amount as u128)
+        .checked_add(syndicate.total_withdrawn as u128)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    let share = vault_total
+        .checked_mul(syndicate_member.contributed as u128)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / syndicate.total_contributed as u128;
+    let amount = (share as u64).saturating_sub(syndicate_member.withdrawn);
+    require!(amount > 0, ErrorCode::NothingToWithdraw);
+
+    syndicate_member.withdrawn += amount;
+    syndicate.total_withdrawn += amount;
+
+    let syndicate_id_bytes = syndicate_id.to_le_bytes();
+    pda_owned_token_accounts::withdraw(
+        amount,
+        &[b"syndicate_token_account", &syndicate_id_bytes],
+        ctx.bumps.syndicate_token_account,
+        &ctx.accounts.syndicate_token_account,
+        &ctx.accounts.payer_token_account,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+
+    msg!(
+        "Member {} withdrew {} tokens from syndicate {}",
+        syndicate_member.member, amount, syndicate_id
+    );
+    Ok(())
+}
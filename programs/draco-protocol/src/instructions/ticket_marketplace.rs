@@ -0,0 +1,340 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{ Mint, TokenInterface, TokenAccount}};
+
+use crate::instructions;
+use crate::state::lottery::{LotteryState, LotteryStatus};
+use crate::state::ticket::Ticket;
+use crate::state::participant::Participant;
+use crate::state::ticket_listing::TicketListing;
+use crate::state::protocol_error::ErrorCode;
+
+use crate::instructions::{lottery, pda_owned_token_accounts};
+
+use crate::constants::TICKET_SALE_FEE_BPS;
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, combination: String)]
+pub struct ListTicket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    // Escrowed into the listing until it's sold or delisted
+    #[account(
+        mut,
+        seeds = [
+            b"ticket".as_ref(),
+            lottery_id.to_le_bytes().as_ref(),
+            ticket.original_participant.to_bytes().as_ref(),
+            combination.as_ref()
+            ],
+        bump,
+    )]
+    pub ticket: Account<'info, Ticket>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"ticket_listing".as_ref(),
+            ticket.key().as_ref()
+        ],
+        bump,
+        space = 8 + TicketListing::INIT_SPACE,
+    )]
+    pub ticket_listing: Account<'info, TicketListing>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(),
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(lottery_id: u64, combination: String)]
+pub struct BuyListedTicket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Only receives the payment and the listing rent, it must match the listing seller.
+    #[account(
+        mut,
+        address = ticket_listing.seller
+    )]
+    pub seller: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = seller,
+        associated_token::token_program = token_program
+    )]
+    pub seller_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Only receives the rent of the sold ticket, it must match the payer of the ticket.
+    #[account(
+        mut,
+        address = ticket.rent_payer
+    )]
+    pub ticket_rent_payer: UncheckedAccount<'info>,
+
+    // Closed on sale, the ticket moves to the buyer's own ticket account
+    #[account(
+        mut,
+        close = ticket_rent_payer,
+        seeds = [
+            b"ticket".as_ref(),
+            lottery_id.to_le_bytes().as_ref(),
+            ticket.original_participant.to_bytes().as_ref(),
+            combination.as_ref()
+            ],
+        bump,
+        constraint = ticket.participant == ticket_listing.key() @ ErrorCode::NotTicketOwner,
+    )]
+    pub ticket: Account<'info, Ticket>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [
+            b"ticket".as_ref(),
+            lottery_id.to_le_bytes().as_ref(),
+            payer.key().to_bytes().as_ref(),
+            combination.as_ref()
+            ],
+        bump,
+        space = 8 + Ticket::INIT_SPACE,
+        constraint = payer.key() != ticket_listing.seller @ ErrorCode::NotTicketOwner,
+    )]
+    pub buyer_ticket: Account<'info, Ticket>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"participant".as_ref(),
+            lottery_id.to_le_bytes().as_ref(),
+            ticket_listing.seller.to_bytes().as_ref(),
+            ],
+        bump,
+    )]
+    pub seller_participant: Account<'info, Participant>,
+
+    // The ticket counts against the buyer's caps like any bought ticket
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [
+            b"participant".as_ref(),
+            lottery_id.to_le_bytes().as_ref(),
+            payer.key().to_bytes().as_ref(),
+            ],
+        bump,
+        space = 8 + Participant::INIT_SPACE,
+    )]
+    pub buyer_participant: Account<'info, Participant>,
+
+    #[account(
+        mut,
+        close = seller,
+        seeds = [
+            b"ticket_listing".as_ref(),
+            ticket.key().as_ref()
+        ],
+        bump,
+    )]
+    pub ticket_listing: Account<'info, TicketListing>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"lottery_state".as_ref(),
+            lottery_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub lottery_state: Account<'info, LotteryState>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct DelistTicket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Only receives the listing rent, it must match the listing seller.
+    #[account(
+        mut,
+        address = ticket_listing.seller
+    )]
+    pub seller: UncheckedAccount<'info>,
+
+    // Released from the listing escrow back to the seller
+    #[account(
+        mut,
+        address = ticket_listing.ticket
+    )]
+    pub ticket: Account<'info, Ticket>,
+
+    #[account(
+        mut,
+        close = seller,
+        seeds = [
+            b"ticket_listing".as_ref(),
+            ticket_listing.ticket.as_ref()
+        ],
+        bump,
+    )]
+    pub ticket_listing: Account<'info, TicketListing>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn list(ctx: Context<ListTicket>, lottery_id: u64, _combination: String, price: u64) -> Result<()> {
+    msg!("Listing ticket for {} DRACO", price);
+    require!(price > 0, ErrorCode::InvalidAmount);
+
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    lottery_state.sync_status(&Clock::get()?)?;
+    lottery_state.require_open()?;
+    // NFT tickets follow their token, they can be traded on any NFT marketplace instead
+    require!(!lottery_state.ticket_nft_mode, ErrorCode::TicketNotListable);
+
+    let ticket = &mut ctx.accounts.ticket;
+    require!(ticket.participant == ctx.accounts.payer.key(), ErrorCode::NotTicketOwner);
+
+    let ticket_listing = &mut ctx.accounts.ticket_listing;
+    ticket_listing.lottery_id = lottery_id;
+    ticket_listing.ticket = ticket.key();
+    ticket_listing.seller = ticket.participant;
+    ticket_listing.price = price;
+    ticket_listing.expires_at = lottery_state.sales_close_datetime();
+    // The seller can't claim, refund or top up the ticket while it's listed
+    ticket.participant = ticket_listing.key();
+
+    msg!(
+        "Ticket {} of lottery_id {} listed for {} DRACO until {}",
+        ticket.combination, lottery_id, price, ticket_listing.expires_at
+    );
+    Ok(())
+}
+
+pub fn buy_listed(ctx: Context<BuyListedTicket>, lottery_id: u64, combination: String) -> Result<()> {
+    msg!("Buying listed ticket");
+    let lottery_state = &mut ctx.accounts.lottery_state;
+    lottery_state.sync_status(&Clock::get()?)?;
+    require!(lottery_state.status == LotteryStatus::Open, ErrorCode::TicketListingExpired);
+
+    let ticket_listing = &ctx.accounts.ticket_listing;
+    let scaled_price = instructions::utils::get_scaled_amount(
+        ticket_listing.price,
+        ctx.accounts.token_mint.decimals
+    )?;
+    let fee = scaled_price
+        .checked_mul(TICKET_SALE_FEE_BPS)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / 10_000;
+
+    pda_owned_token_accounts::contribute(
+        scaled_price - fee,
+        &ctx.accounts.payer_token_account,
+        &ctx.accounts.seller_token_account,
+        &ctx.accounts.payer,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+    if fee > 0 {
+        pda_owned_token_accounts::contribute(
+            fee,
+            &ctx.accounts.payer_token_account,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.payer,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+        )?;
+    }
+
+    let buyer = ctx.accounts.payer.key();
+    let ticket = &ctx.accounts.ticket;
+    let buyer_ticket = &mut ctx.accounts.buyer_ticket;
+    let previous_amount = if buyer_ticket.is_initialized {
+        // A combination the buyer already holds is merged, unless it's listed itself
+        require!(buyer_ticket.participant == buyer, ErrorCode::NotTicketOwner);
+        buyer_ticket.amount
+    } else {
+        0
+    };
+    let ticket_amount = previous_amount
+        .checked_add(ticket.amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    let seller_units = lottery_state.ticket_units(ticket.amount);
+    let buyer_units = lottery_state.ticket_units(ticket_amount) - lottery_state.ticket_units(previous_amount);
+    lottery::remove_ticket_units(lottery_state, &mut ctx.accounts.seller_participant, seller_units);
+    lottery::add_ticket_units(lottery_state, &mut ctx.accounts.buyer_participant, buyer, buyer, buyer_units)?;
+    lottery::record_ticket_transfer(lottery_state, &ticket_listing.seller, &buyer, &combination, ticket.amount);
+
+    buyer_ticket.lottery_id = lottery_id;
+    buyer_ticket.combination = combination;
+    buyer_ticket.amount = ticket_amount;
+    buyer_ticket.participant = buyer;
+    buyer_ticket.original_participant = buyer;
+    if !buyer_ticket.is_initialized {
+        buyer_ticket.rent_payer = buyer;
+    }
+    buyer_ticket.is_claimed = false;
+    buyer_ticket.is_initialized = true;
+
+    msg!(
+        "Ticket {} of lottery_id {} sold by {} to {} for {} DRACO",
+        buyer_ticket.combination, buyer_ticket.lottery_id, ticket_listing.seller, buyer, ticket_listing.price
+    );
+    Ok(())
+}
+
+pub fn delist(ctx: Context<DelistTicket>) -> Result<()> {
+    msg!("Delisting ticket");
+    let ticket_listing = &ctx.accounts.ticket_listing;
+
+    // Anyone can clean up an expired listing, the rent still goes back to the seller
+    require!(
+        ctx.accounts.payer.key() == ticket_listing.seller
+            || ticket_listing.expires_at <= Clock::get()?.unix_timestamp,
+        ErrorCode::TicketListingNotExpired
+    );
+    ctx.accounts.ticket.participant = ticket_listing.seller;
+
+    msg!(
+        "Listing of ticket {} from lottery_id {} removed",
+        ticket_listing.ticket, ticket_listing.lottery_id
+    );
+    Ok(())
+}
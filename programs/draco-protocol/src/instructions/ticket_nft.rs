@@ -0,0 +1,159 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::token_interface::{
+    burn, mint_to, token_metadata_initialize, token_metadata_update_field,
+    spl_token_metadata_interface::state::{Field, TokenMetadata},
+    spl_pod::optional_keys::OptionalNonZeroPubkey,
+    Burn, Mint, MintTo, TokenAccount, TokenInterface,
+    TokenMetadataInitialize, TokenMetadataUpdateField,
+};
+
+use crate::constants::{TICKET_NFT_NAME, TICKET_NFT_SYMBOL, TICKET_NFT_URI};
+use crate::state::protocol_error::ErrorCode;
+use crate::state::ticket::Ticket;
+
+fn ticket_nft_metadata(mint: Pubkey, lottery_id: u64, combination: &str, amount: u64) -> TokenMetadata {
+    TokenMetadata {
+        update_authority: OptionalNonZeroPubkey(mint),
+        mint,
+        name: TICKET_NFT_NAME.to_string(),
+        symbol: TICKET_NFT_SYMBOL.to_string(),
+        uri: TICKET_NFT_URI.to_string(),
+        additional_metadata: vec![
+            ("lottery_id".to_string(), lottery_id.to_string()),
+            ("combination".to_string(), combination.to_string()),
+            ("amount".to_string(), amount.to_string()),
+        ],
+    }
+}
+
+pub fn mint_ticket_nft<'info>(
+    ticket: &Account<'info, Ticket>,
+    ticket_mint_bump: u8,
+    ticket_mint: &InterfaceAccount<'info, Mint>,
+    ticket_holder_account: &InterfaceAccount<'info, TokenAccount>,
+    payer: &Signer<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let ticket_key = ticket.key();
+    let bump_slice = [ticket_mint_bump];
+    let signer_seeds: &[&[&[u8]]] = &[&[b"ticket_mint", ticket_key.as_ref(), &bump_slice]];
+    let mint_info = ticket_mint.to_account_info();
+
+    // The metadata is reallocated into the mint itself, so it has to hold enough
+    // lamports upfront. The amount is sized at its widest so top ups never need more.
+    let metadata_len = ticket_nft_metadata(ticket_mint.key(), ticket.lottery_id, &ticket.combination, u64::MAX)
+        .tlv_size_of()?;
+    let required_lamports = Rent::get()?.minimum_balance(mint_info.data_len() + metadata_len);
+    let missing_lamports = required_lamports.saturating_sub(mint_info.lamports());
+    if missing_lamports > 0 {
+        transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                Transfer {
+                    from: payer.to_account_info(),
+                    to: mint_info.clone(),
+                },
+            ),
+            missing_lamports,
+        )?;
+    }
+
+    token_metadata_initialize(
+        CpiContext::new(
+            token_program.to_account_info(),
+            TokenMetadataInitialize {
+                program_id: token_program.to_account_info(),
+                metadata: mint_info.clone(),
+                update_authority: mint_info.clone(),
+                mint_authority: mint_info.clone(),
+                mint: mint_info.clone(),
+            },
+        ).with_signer(signer_seeds),
+        TICKET_NFT_NAME.to_string(),
+        TICKET_NFT_SYMBOL.to_string(),
+        TICKET_NFT_URI.to_string(),
+    )?;
+
+    let metadata = ticket_nft_metadata(ticket_mint.key(), ticket.lottery_id, &ticket.combination, ticket.amount);
+    for (key, value) in metadata.additional_metadata {
+        update_ticket_nft_field(key, value, ticket_key, ticket_mint_bump, ticket_mint, token_program)?;
+    }
+
+    // The mint authority stays with the mint PDA, which only mints when its ticket is created
+    mint_to(
+        CpiContext::new(
+            token_program.to_account_info(),
+            MintTo {
+                mint: mint_info.clone(),
+                to: ticket_holder_account.to_account_info(),
+                authority: mint_info,
+            },
+        ).with_signer(signer_seeds),
+        1,
+    )?;
+
+    Ok(())
+}
+
+pub fn update_ticket_nft_field<'info>(
+    key: String,
+    value: String,
+    ticket_key: Pubkey,
+    ticket_mint_bump: u8,
+    ticket_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let bump_slice = [ticket_mint_bump];
+    let signer_seeds: &[&[&[u8]]] = &[&[b"ticket_mint", ticket_key.as_ref(), &bump_slice]];
+
+    token_metadata_update_field(
+        CpiContext::new(
+            token_program.to_account_info(),
+            TokenMetadataUpdateField {
+                program_id: token_program.to_account_info(),
+                metadata: ticket_mint.to_account_info(),
+                update_authority: ticket_mint.to_account_info(),
+            },
+        ).with_signer(signer_seeds),
+        Field::Key(key),
+        value,
+    )
+}
+
+pub fn enforce_ticket_nft_holder(
+    ticket_mint: &Option<InterfaceAccount<'_, Mint>>,
+    ticket_holder_account: &Option<InterfaceAccount<'_, TokenAccount>>,
+    holder: Pubkey,
+) -> Result<()> {
+    let (Some(ticket_mint), Some(ticket_holder_account)) = (ticket_mint, ticket_holder_account) else {
+        return err!(ErrorCode::TicketNftModeMismatch);
+    };
+    require!(
+        ticket_holder_account.mint == ticket_mint.key()
+            && ticket_holder_account.owner == holder
+            && ticket_holder_account.amount == 1,
+        ErrorCode::NotTicketHolder
+    );
+    Ok(())
+}
+
+pub fn burn_ticket_nft<'info>(
+    ticket_mint: &InterfaceAccount<'info, Mint>,
+    ticket_holder_account: &InterfaceAccount<'info, TokenAccount>,
+    holder: &Signer<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    burn(
+        CpiContext::new(
+            token_program.to_account_info(),
+            Burn {
+                mint: ticket_mint.to_account_info(),
+                from: ticket_holder_account.to_account_info(),
+                authority: holder.to_account_info(),
+            },
+        ),
+        1,
+    )
+}
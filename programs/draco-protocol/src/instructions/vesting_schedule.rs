@@ -0,0 +1,332 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{ Mint, TokenInterface, TokenAccount}};
+
+use crate::instructions;
+use crate::instructions::pda_owned_token_accounts;
+use crate::state::protocol_authority::ProtocolAuthority;
+use crate::state::protocol_error::ErrorCode;
+pub use crate::state::vesting_schedule::{VestingRelease, VestingSchedule};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct VestingScheduleParams {
+    pub beneficiary: Pubkey,
+    // In whole DRACO
+    pub total_amount: u64,
+    pub start_datetime: i64,
+    pub cliff_datetime: i64,
+    pub release: VestingRelease,
+    // Treasury funded schedules need the authority, the others are funded by the payer
+    pub from_treasury: bool,
+    // Only treasury funded schedules can be revocable
+    pub revocable: bool,
+}
+
+#[derive(Accounts)]
+#[instruction(schedule_id: u64)]
+pub struct CreateVestingSchedule<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    // Only for schedules funded by the payer
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"vesting_schedule".as_ref(),
+            payer.key().to_bytes().as_ref(),
+            schedule_id.to_le_bytes().as_ref()
+        ],
+        bump,
+        space = 8 + VestingSchedule::INIT_SPACE,
+    )]
+    pub vesting_schedule: Account<'info, VestingSchedule>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            b"vesting_schedule_token_account".as_ref(),
+            vesting_schedule.key().as_ref()
+        ],
+        bump,
+        token::mint = token_mint,
+        token::authority = vesting_schedule_token_account,
+    )]
+    pub vesting_schedule_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseVested<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Only receives the released tokens, it must match the schedule beneficiary.
+    #[account(address = vesting_schedule.beneficiary)]
+    pub beneficiary: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = beneficiary,
+        associated_token::token_program = token_program
+    )]
+    pub beneficiary_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"vesting_schedule".as_ref(),
+            vesting_schedule.creator.to_bytes().as_ref(),
+            vesting_schedule.schedule_id.to_le_bytes().as_ref()
+        ],
+        bump,
+    )]
+    pub vesting_schedule: Account<'info, VestingSchedule>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"vesting_schedule_token_account".as_ref(),
+            vesting_schedule.key().as_ref()
+        ],
+        bump
+    )]
+    pub vesting_schedule_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeVestingSchedule<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"vesting_schedule".as_ref(),
+            vesting_schedule.creator.to_bytes().as_ref(),
+            vesting_schedule.schedule_id.to_le_bytes().as_ref()
+        ],
+        bump,
+    )]
+    pub vesting_schedule: Account<'info, VestingSchedule>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"vesting_schedule_token_account".as_ref(),
+            vesting_schedule.key().as_ref()
+        ],
+        bump
+    )]
+    pub vesting_schedule_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_authority".as_ref()],
+        bump
+    )]
+    pub protocol_authority: Account<'info, ProtocolAuthority>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn create_schedule(
+    ctx: Context<CreateVestingSchedule>,
+    schedule_id: u64,
+    params: VestingScheduleParams,
+) -> Result<()> {
+    msg!("Creating vesting schedule");
+    let scaled_amount = init_schedule(
+        &mut ctx.accounts.vesting_schedule,
+        schedule_id,
+        ctx.accounts.payer.key(),
+        &params,
+        ctx.accounts.token_mint.decimals,
+    )?;
+
+    if params.from_treasury {
+        pda_owned_token_accounts::withdraw(
+            scaled_amount,
+            &[b"treasury"],
+            ctx.bumps.treasury_token_account,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.vesting_schedule_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+        )?;
+    } else {
+        let payer_token_account = ctx.accounts.payer_token_account
+            .as_ref()
+            .ok_or(ErrorCode::InvalidVestingSchedule)?;
+        pda_owned_token_accounts::contribute(
+            scaled_amount,
+            payer_token_account,
+            &ctx.accounts.vesting_schedule_token_account,
+            &ctx.accounts.payer,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+        )?;
+    }
+
+    msg!(
+        "Vesting schedule {} of {} DRACO created for {}",
+        schedule_id, params.total_amount, params.beneficiary
+    );
+    Ok(())
+}
+
+/// Validates the params and fills in a new schedule, funding its vault is left to the caller.
+/// Returns the total amount in base units.
+pub fn init_schedule(
+    vesting_schedule: &mut VestingSchedule,
+    schedule_id: u64,
+    creator: Pubkey,
+    params: &VestingScheduleParams,
+    decimals: u8,
+) -> Result<u64> {
+    require!(params.total_amount > 0, ErrorCode::InvalidAmount);
+    // Clawbacks go to the treasury, so only treasury funded schedules can be revoked
+    require!(!params.revocable || params.from_treasury, ErrorCode::VestingScheduleNotRevocable);
+    require!(params.cliff_datetime >= params.start_datetime, ErrorCode::InvalidVestingSchedule);
+    match params.release {
+        VestingRelease::Linear { end_datetime } => require!(
+            end_datetime > params.start_datetime && end_datetime >= params.cliff_datetime,
+            ErrorCode::InvalidVestingSchedule
+        ),
+        VestingRelease::Periodic { period, amount_per_period } => require!(
+            period > 0 && amount_per_period > 0,
+            ErrorCode::InvalidVestingSchedule
+        ),
+    }
+
+    let scaled_amount = instructions::utils::get_scaled_amount(params.total_amount, decimals)?;
+    // Per period amounts are given in whole DRACO like every other amount
+    let release = match params.release {
+        VestingRelease::Periodic { period, amount_per_period } => VestingRelease::Periodic {
+            period,
+            amount_per_period: instructions::utils::get_scaled_amount(amount_per_period, decimals)?,
+        },
+        linear => linear,
+    };
+
+    vesting_schedule.schedule_id = schedule_id;
+    vesting_schedule.creator = creator;
+    vesting_schedule.beneficiary = params.beneficiary;
+    vesting_schedule.funded_from_treasury = params.from_treasury;
+    vesting_schedule.total_amount = scaled_amount;
+    vesting_schedule.released_amount = 0;
+    vesting_schedule.start_datetime = params.start_datetime;
+    vesting_schedule.cliff_datetime = params.cliff_datetime;
+    vesting_schedule.release = release;
+    vesting_schedule.revocable = params.revocable;
+    vesting_schedule.revoked_at = None;
+    vesting_schedule.clawed_back_amount = 0;
+    Ok(scaled_amount)
+}
+
+pub fn release(ctx: Context<ReleaseVested>) -> Result<()> {
+    msg!("Releasing vested tokens");
+    let vesting_schedule = &mut ctx.accounts.vesting_schedule;
+    let vested_amount = vesting_schedule.vested_amount(Clock::get()?.unix_timestamp);
+    let amount = vested_amount.saturating_sub(vesting_schedule.released_amount);
+    require!(amount > 0, ErrorCode::NothingToWithdraw);
+
+    vesting_schedule.released_amount = vested_amount;
+
+    let vesting_schedule_key = vesting_schedule.key();
+    pda_owned_token_accounts::withdraw(
+        amount,
+        &[b"vesting_schedule_token_account", vesting_schedule_key.as_ref()],
+        ctx.bumps.vesting_schedule_token_account,
+        &ctx.accounts.vesting_schedule_token_account,
+        &ctx.accounts.beneficiary_token_account,
+        &ctx.accounts.token_mint,
+        &ctx.accounts.token_program,
+    )?;
+
+    msg!(
+        "Released {} of vesting schedule {} to {}",
+        amount, vesting_schedule.schedule_id, vesting_schedule.beneficiary
+    );
+    Ok(())
+}
+
+pub fn revoke(ctx: Context<RevokeVestingSchedule>) -> Result<()> {
+    msg!("Revoking vesting schedule");
+    let current_timestamp = Clock::get()?.unix_timestamp;
+    let vesting_schedule = &mut ctx.accounts.vesting_schedule;
+    require!(vesting_schedule.revocable, ErrorCode::VestingScheduleNotRevocable);
+    require!(vesting_schedule.revoked_at.is_none(), ErrorCode::VestingScheduleRevoked);
+
+    // The vested part stays in the vault for the beneficiary to release
+    let vested_amount = vesting_schedule.vested_amount(current_timestamp);
+    let unvested_amount = vesting_schedule.total_amount - vested_amount;
+    vesting_schedule.revoked_at = Some(current_timestamp);
+    vesting_schedule.clawed_back_amount = unvested_amount;
+
+    if unvested_amount > 0 {
+        let vesting_schedule_key = vesting_schedule.key();
+        pda_owned_token_accounts::withdraw(
+            unvested_amount,
+            &[b"vesting_schedule_token_account", vesting_schedule_key.as_ref()],
+            ctx.bumps.vesting_schedule_token_account,
+            &ctx.accounts.vesting_schedule_token_account,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+        )?;
+    }
+
+    msg!(
+        "Vesting schedule {} of {} revoked, {} clawed back and {} left to release",
+        vesting_schedule.schedule_id, vesting_schedule.beneficiary,
+        unvested_amount, vested_amount - vesting_schedule.released_amount
+    );
+    Ok(())
+}
@@ -1,6 +1,7 @@
 pub mod instructions;
 pub mod state;
 pub mod constants;
+pub mod events;
 
 use anchor_lang::prelude::*;
 
@@ -24,7 +25,7 @@ pub mod draco_protocol {
         )?;
 
         instructions::initialize_treasury::contribute_treasury_account(&ctx)?;
-        instructions::initialize_treasury::contribute_six_month_cliff_account(ctx)?;
+        instructions::initialize_treasury::create_treasury_vesting_schedule(ctx)?;
         Ok(())
     }
 
@@ -65,17 +66,36 @@ pub mod draco_protocol {
         Ok(())
     }
 
-    pub fn transfer_out_from_six_month_cliff(ctx: Context<TransferOutFromSixMonthCliff>) -> Result<()> {
-        msg!("draco_protocol::transfer_out_from_six_month_cliff");
+    pub fn create_vesting_schedule(ctx: Context<CreateVestingSchedule>, schedule_id: u64, params: VestingScheduleParams) -> Result<()> {
+        msg!("draco_protocol::create_vesting_schedule");
+        // Anyone can lock their own tokens, only the authority can lock treasury funds
+        if params.from_treasury {
+            instructions::protocol_authority::enforce_protocol_authority(
+                ctx.accounts.protocol_authority.authority,
+                ctx.accounts.payer.key(),
+            )?;
+        }
+        instructions::vesting_schedule::create_schedule(ctx, schedule_id, params)?;
+        Ok(())
+    }
+
+    pub fn release_vested(ctx: Context<ReleaseVested>) -> Result<()> {
+        msg!("draco_protocol::release_vested");
+        instructions::vesting_schedule::release(ctx)?;
+        Ok(())
+    }
+
+    pub fn revoke_vesting_schedule(ctx: Context<RevokeVestingSchedule>) -> Result<()> {
+        msg!("draco_protocol::revoke_vesting_schedule");
         instructions::protocol_authority::enforce_protocol_authority(
             ctx.accounts.protocol_authority.authority,
             ctx.accounts.payer.key(),
         )?;
-        instructions::six_month_cliff::transfer_out(ctx)?;
+        instructions::vesting_schedule::revoke(ctx)?;
         Ok(())
     }
 
-    pub fn start_lottery(ctx: Context<StartLottery>, lottery_id: u64, lottery_name: String, lottery_description: String, lottery_type: u8, lottery_start_datetime: i64, lottery_end_datetime: i64, initial_prize_pool: u64, min_tokens_per_participant: u64) -> Result<()> {
+    pub fn start_lottery(ctx: Context<StartLottery>, lottery_id: u64, lottery_name: String, lottery_description: String, lottery_type: u8, lottery_start_datetime: i64, lottery_end_datetime: i64, initial_prize_pool: u64, min_tokens_per_participant: u64, config: LotteryConfig) -> Result<()> {
         msg!("draco_protocol::start_lottery");
         instructions::protocol_authority::enforce_protocol_authority(
             ctx.accounts.protocol_authority.authority,
@@ -90,20 +110,37 @@ pub mod draco_protocol {
             lottery_start_datetime, 
             lottery_end_datetime, 
             initial_prize_pool, 
-            min_tokens_per_participant
+            min_tokens_per_participant,
+            config,
         )?;
         Ok(())
     }
 
-    pub fn buy_lottery_ticket(ctx: Context<BuyLotteryTicket>, lottery_id: u64, combination: String, amount: u64) -> Result<()> {
+    pub fn buy_lottery_ticket(mut ctx: Context<BuyLotteryTicket>, lottery_id: u64, combination: String, amount: u64) -> Result<()> {
         msg!("draco_protocol::buy_lottery_ticket");
-        instructions::lottery::enforce_lottery_active(&ctx)?;
+        instructions::lottery::enforce_lottery_active(&mut ctx)?;
         let _ = instructions::lottery::verify_combination(&combination)?;
-        instructions::lottery::verify_amount_on_type(&ctx, amount.clone())?;
+        instructions::lottery::verify_amount_on_type(&ctx.accounts.lottery_state, amount.clone())?;
         instructions::lottery::buy_ticket(ctx, lottery_id, amount, combination)?;
         Ok(())
     }
 
+    pub fn close_lottery_sales(ctx: Context<CloseLotterySales>, lottery_id: u64) -> Result<()> {
+        msg!("draco_protocol::close_lottery_sales");
+        instructions::lottery::close_sales(ctx, lottery_id)?;
+        Ok(())
+    }
+
+    pub fn finalize_ticket_set(ctx: Context<FinalizeLotteryTicketSet>, lottery_id: u64) -> Result<()> {
+        msg!("draco_protocol::finalize_ticket_set");
+        instructions::protocol_authority::enforce_protocol_authority(
+            ctx.accounts.protocol_authority.authority,
+            ctx.accounts.payer.key(),
+        )?;
+        instructions::lottery::finalize_tickets(ctx, lottery_id)?;
+        Ok(())
+    }
+
     pub fn commit_lottery_randomness(ctx: Context<CommitLotteryRandomness>, lottery_id: u64) -> Result<()> {
         msg!("draco_protocol::commit_lottery_randomness");
         instructions::protocol_authority::enforce_protocol_authority(
@@ -130,6 +167,38 @@ pub mod draco_protocol {
         Ok(())
     }
 
+    pub fn preview_prize(ctx: Context<PreviewPrize>, lottery_id: u64, combination: String) -> Result<PrizeBreakdown> {
+        msg!("draco_protocol::preview_prize");
+        let prize_breakdown = instructions::lottery::preview(ctx, lottery_id, combination)?;
+        Ok(prize_breakdown)
+    }
+
+    pub fn claim_lottery_prizes<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimLotteryPrizes<'info>>,
+        lottery_id: u64,
+        close_tickets: bool,
+    ) -> Result<()> {
+        msg!("draco_protocol::claim_lottery_prizes");
+        instructions::lottery::claim_prizes(ctx, lottery_id, close_tickets)?;
+        Ok(())
+    }
+
+    pub fn set_payout_bounty(ctx: Context<SetPayoutBounty>, payout_bounty: u64) -> Result<()> {
+        msg!("draco_protocol::set_payout_bounty");
+        instructions::protocol_authority::enforce_protocol_authority(
+            ctx.accounts.protocol_authority.authority,
+            ctx.accounts.payer.key(),
+        )?;
+        instructions::payout::set_bounty(ctx, payout_bounty)?;
+        Ok(())
+    }
+
+    pub fn payout_ticket(ctx: Context<PayoutTicket>, lottery_id: u64, combination: String) -> Result<()> {
+        msg!("draco_protocol::payout_ticket");
+        instructions::payout::pay_out(ctx, lottery_id, combination)?;
+        Ok(())
+    }
+
     pub fn close_lottery(ctx: Context<CloseLottery>, lottery_id: u64) -> Result<()> {
         msg!("draco_protocol::close_lottery");
         instructions::protocol_authority::enforce_protocol_authority(
@@ -140,21 +209,250 @@ pub mod draco_protocol {
         Ok(())
     }
 
-    pub fn create_airdrop(ctx: Context<CreateAirdrop>, airdrop_id: u64, name: String, supply: u64, amount_per_claim: u64, start_datetime: i64, end_datetime: i64) -> Result<()> {
+    pub fn expire_unclaimed_prizes(ctx: Context<ExpireUnclaimedPrizes>, lottery_id: u64) -> Result<()> {
+        msg!("draco_protocol::expire_unclaimed_prizes");
+        instructions::lottery::expire_unclaimed(ctx, lottery_id)?;
+        Ok(())
+    }
+
+    pub fn extend_lottery_claim_deadline(ctx: Context<ExtendLotteryClaimDeadline>, lottery_id: u64, claim_deadline: i64) -> Result<()> {
+        msg!("draco_protocol::extend_lottery_claim_deadline");
+        instructions::protocol_authority::enforce_protocol_authority(
+            ctx.accounts.protocol_authority.authority,
+            ctx.accounts.payer.key(),
+        )?;
+        instructions::lottery::extend_claim_deadline(ctx, lottery_id, claim_deadline)?;
+        Ok(())
+    }
+
+    pub fn update_scheduled_lottery(
+        ctx: Context<UpdateScheduledLottery>,
+        lottery_id: u64,
+        lottery_name: Option<String>,
+        lottery_description: Option<String>,
+        lottery_start_datetime: Option<i64>,
+        lottery_end_datetime: Option<i64>,
+        min_tokens_per_participant: Option<u64>,
+    ) -> Result<()> {
+        msg!("draco_protocol::update_scheduled_lottery");
+        instructions::protocol_authority::enforce_protocol_authority(
+            ctx.accounts.protocol_authority.authority,
+            ctx.accounts.payer.key(),
+        )?;
+        instructions::lottery::update_scheduled(
+            ctx,
+            lottery_id,
+            lottery_name,
+            lottery_description,
+            lottery_start_datetime,
+            lottery_end_datetime,
+            min_tokens_per_participant,
+        )?;
+        Ok(())
+    }
+
+    pub fn top_up_lottery_prize_pool(ctx: Context<TopUpLotteryPrizePool>, lottery_id: u64, amount: u64) -> Result<()> {
+        msg!("draco_protocol::top_up_lottery_prize_pool");
+        instructions::protocol_authority::enforce_protocol_authority(
+            ctx.accounts.protocol_authority.authority,
+            ctx.accounts.payer.key(),
+        )?;
+        instructions::lottery::top_up_prize_pool(ctx, lottery_id, amount)?;
+        Ok(())
+    }
+
+    pub fn cancel_lottery(ctx: Context<CancelLottery>, lottery_id: u64) -> Result<()> {
+        msg!("draco_protocol::cancel_lottery");
+        instructions::protocol_authority::enforce_protocol_authority(
+            ctx.accounts.protocol_authority.authority,
+            ctx.accounts.payer.key(),
+        )?;
+        instructions::lottery::cancel(ctx, lottery_id)?;
+        Ok(())
+    }
+
+    pub fn refund_ticket(ctx: Context<RefundTicket>, lottery_id: u64, combination: String) -> Result<()> {
+        msg!("draco_protocol::refund_ticket");
+        instructions::lottery::refund(ctx, lottery_id, combination)?;
+        Ok(())
+    }
+
+    pub fn close_lottery_ticket(ctx: Context<CloseLotteryTicket>, lottery_id: u64, combination: String) -> Result<()> {
+        msg!("draco_protocol::close_lottery_ticket");
+        instructions::lottery::close_ticket(ctx, lottery_id, combination)?;
+        Ok(())
+    }
+
+    pub fn close_lottery_accounts(ctx: Context<CloseLotteryAccounts>, lottery_id: u64) -> Result<()> {
+        msg!("draco_protocol::close_lottery_accounts");
+        instructions::protocol_authority::enforce_protocol_authority(
+            ctx.accounts.protocol_authority.authority,
+            ctx.accounts.payer.key(),
+        )?;
+        instructions::lottery::close_accounts(ctx, lottery_id)?;
+        Ok(())
+    }
+
+    pub fn sponsor_lottery(ctx: Context<SponsorLottery>, lottery_id: u64, amount: u64) -> Result<()> {
+        msg!("draco_protocol::sponsor_lottery");
+        instructions::lottery_sponsorship::sponsor(ctx, lottery_id, amount)?;
+        Ok(())
+    }
+
+    pub fn refund_lottery_sponsorship(ctx: Context<RefundLotterySponsorship>, lottery_id: u64) -> Result<()> {
+        msg!("draco_protocol::refund_lottery_sponsorship");
+        instructions::lottery_sponsorship::refund_sponsor(ctx, lottery_id)?;
+        Ok(())
+    }
+
+    pub fn list_ticket(ctx: Context<ListTicket>, lottery_id: u64, combination: String, price: u64) -> Result<()> {
+        msg!("draco_protocol::list_ticket");
+        instructions::ticket_marketplace::list(ctx, lottery_id, combination, price)?;
+        Ok(())
+    }
+
+    pub fn buy_listed_ticket(ctx: Context<BuyListedTicket>, lottery_id: u64, combination: String) -> Result<()> {
+        msg!("draco_protocol::buy_listed_ticket");
+        instructions::ticket_marketplace::buy_listed(ctx, lottery_id, combination)?;
+        Ok(())
+    }
+
+    pub fn delist_ticket(ctx: Context<DelistTicket>) -> Result<()> {
+        msg!("draco_protocol::delist_ticket");
+        instructions::ticket_marketplace::delist(ctx)?;
+        Ok(())
+    }
+
+    pub fn create_syndicate(ctx: Context<CreateSyndicate>, syndicate_id: u64, lottery_id: u64) -> Result<()> {
+        msg!("draco_protocol::create_syndicate");
+        instructions::syndicate::new_syndicate(ctx, syndicate_id, lottery_id)?;
+        Ok(())
+    }
+
+    pub fn contribute_to_syndicate(ctx: Context<ContributeToSyndicate>, syndicate_id: u64, amount: u64) -> Result<()> {
+        msg!("draco_protocol::contribute_to_syndicate");
+        instructions::syndicate::add_contribution(ctx, syndicate_id, amount)?;
+        Ok(())
+    }
+
+    pub fn buy_syndicate_ticket(ctx: Context<BuySyndicateTicket>, syndicate_id: u64, combination: String, amount: u64) -> Result<()> {
+        msg!("draco_protocol::buy_syndicate_ticket");
+        instructions::syndicate::buy_ticket_for_syndicate(ctx, syndicate_id, combination, amount)?;
+        Ok(())
+    }
+
+    pub fn claim_syndicate_prize(ctx: Context<ClaimSyndicatePrize>, syndicate_id: u64, combination: String) -> Result<()> {
+        msg!("draco_protocol::claim_syndicate_prize");
+        instructions::syndicate::claim_prize_for_syndicate(ctx, syndicate_id, combination)?;
+        Ok(())
+    }
+
+    pub fn refund_syndicate_ticket(ctx: Context<RefundSyndicateTicket>, syndicate_id: u64, combination: String) -> Result<()> {
+        msg!("draco_protocol::refund_syndicate_ticket");
+        instructions::syndicate::refund_ticket_for_syndicate(ctx, syndicate_id, combination)?;
+        Ok(())
+    }
+
+    pub fn withdraw_syndicate_share(ctx: Context<WithdrawSyndicateShare>, syndicate_id: u64) -> Result<()> {
+        msg!("draco_protocol::withdraw_syndicate_share");
+        instructions::syndicate::withdraw_share(ctx, syndicate_id)?;
+        Ok(())
+    }
+
+    pub fn create_airdrop(ctx: Context<CreateAirdrop>, airdrop_id: u64, name: String, supply: u64, start_datetime: i64, end_datetime: i64, config: AirdropConfig) -> Result<()> {
         msg!("draco_protocol::create_airdrop");
         instructions::protocol_authority::enforce_protocol_authority(
             ctx.accounts.protocol_authority.authority,
             ctx.accounts.payer.key(),
         )?;
-        instructions::airdrop::create(ctx, airdrop_id, name, supply, amount_per_claim, start_datetime, end_datetime)?;
+        instructions::airdrop::create(ctx, airdrop_id, name, supply, start_datetime, end_datetime, config)?;
         Ok(())
     }
 
-    pub fn claim_airdrop(ctx: Context<ClaimAirdrop>, airdrop_id: u64) -> Result<()> {
+    pub fn claim_airdrop(ctx: Context<ClaimAirdrop>, airdrop_id: u64, merkle_claim: Option<MerkleClaim>, signed_claim: Option<SignedClaim>) -> Result<()> {
         msg!("draco_protocol::claim_airdrop");
-        instructions::airdrop::claim(ctx, airdrop_id)?;
+        instructions::airdrop::claim(ctx, airdrop_id, merkle_claim, signed_claim)?;
         Ok(())
     }
 
-}
+    pub fn claim_airdrop_into_ticket(ctx: Context<ClaimAirdropIntoTicket>, airdrop_id: u64, merkle_claim: Option<MerkleClaim>, signed_claim: Option<SignedClaim>, lottery_id: u64, combination: String) -> Result<()> {
+        msg!("draco_protocol::claim_airdrop_into_ticket");
+        instructions::airdrop::claim_into_ticket(ctx, airdrop_id, merkle_claim, signed_claim, lottery_id, combination)?;
+        Ok(())
+    }
+
+    pub fn register_for_airdrop(ctx: Context<RegisterForAirdrop>, airdrop_id: u64) -> Result<()> {
+        msg!("draco_protocol::register_for_airdrop");
+        instructions::airdrop::register_balance(ctx, airdrop_id)?;
+        Ok(())
+    }
 
+    pub fn withdraw_vested_airdrop(ctx: Context<WithdrawVestedAirdrop>, airdrop_id: u64) -> Result<()> {
+        msg!("draco_protocol::withdraw_vested_airdrop");
+        instructions::airdrop::withdraw_vested(ctx, airdrop_id)?;
+        Ok(())
+    }
+
+    pub fn extend_airdrop(ctx: Context<UpdateAirdrop>, airdrop_id: u64, end_datetime: i64) -> Result<()> {
+        msg!("draco_protocol::extend_airdrop");
+        instructions::protocol_authority::enforce_protocol_authority(
+            ctx.accounts.protocol_authority.authority,
+            ctx.accounts.payer.key(),
+        )?;
+        instructions::airdrop::extend(ctx, airdrop_id, end_datetime)?;
+        Ok(())
+    }
+
+    pub fn increase_airdrop_supply(ctx: Context<IncreaseAirdropSupply>, airdrop_id: u64, added_supply: u64) -> Result<()> {
+        msg!("draco_protocol::increase_airdrop_supply");
+        instructions::protocol_authority::enforce_protocol_authority(
+            ctx.accounts.protocol_authority.authority,
+            ctx.accounts.payer.key(),
+        )?;
+        instructions::airdrop::add_supply(ctx, airdrop_id, added_supply)?;
+        Ok(())
+    }
+
+    pub fn set_airdrop_paused(ctx: Context<UpdateAirdrop>, airdrop_id: u64, is_paused: bool) -> Result<()> {
+        msg!("draco_protocol::set_airdrop_paused");
+        instructions::protocol_authority::enforce_protocol_authority(
+            ctx.accounts.protocol_authority.authority,
+            ctx.accounts.payer.key(),
+        )?;
+        instructions::airdrop::set_paused(ctx, airdrop_id, is_paused)?;
+        Ok(())
+    }
+
+    pub fn cancel_airdrop(ctx: Context<CancelAirdrop>, airdrop_id: u64) -> Result<()> {
+        msg!("draco_protocol::cancel_airdrop");
+        instructions::protocol_authority::enforce_protocol_authority(
+            ctx.accounts.protocol_authority.authority,
+            ctx.accounts.payer.key(),
+        )?;
+        instructions::airdrop::terminate(ctx, airdrop_id)?;
+        Ok(())
+    }
+
+    pub fn close_airdrop_claimed(ctx: Context<CloseAirdropClaimed>, airdrop_id: u64) -> Result<()> {
+        msg!("draco_protocol::close_airdrop_claimed");
+        instructions::airdrop::close_claimed(ctx, airdrop_id)?;
+        Ok(())
+    }
+
+    pub fn close_airdrop_claim_bitmap(ctx: Context<CloseAirdropClaimBitmap>, airdrop_id: u64, chunk: u64) -> Result<()> {
+        msg!("draco_protocol::close_airdrop_claim_bitmap");
+        instructions::airdrop::close_claim_bitmap(ctx, airdrop_id, chunk)?;
+        Ok(())
+    }
+
+    pub fn close_airdrop(ctx: Context<CloseAirdrop>, airdrop_id: u64) -> Result<()> {
+        msg!("draco_protocol::close_airdrop");
+        instructions::protocol_authority::enforce_protocol_authority(
+            ctx.accounts.protocol_authority.authority,
+            ctx.accounts.payer.key(),
+        )?;
+        instructions::airdrop::close_state(ctx, airdrop_id)?;
+        Ok(())
+    }
+
+}
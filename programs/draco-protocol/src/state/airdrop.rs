@@ -1,5 +1,59 @@
 use anchor_lang::prelude::*;
 
+use crate::constants::AIRDROP_MAX_BALANCE_TIERS;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum AirdropMode {
+    // Anyone can claim amount_per_claim once, see AirdropClaimed
+    Open,
+    // Only the (index, claimer, amount) leaves of the tree can claim, see AirdropClaimBitmap
+    Merkle { merkle_root: [u8; 32] },
+    // Each wallet claims once, with an Ed25519 signature of the signer over the
+    // tagged (airdrop_id, claimer, amount, expiry) in the same transaction, see SignedClaim
+    Signed { signer: Pubkey },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub struct AirdropVesting {
+    // Seconds after the claim before anything unlocks
+    pub cliff_duration: i64,
+    // Seconds after the claim until everything is unlocked
+    pub vesting_duration: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub struct AirdropBalanceTier {
+    // Eligibility balance from which the tier applies, in base units of the required mint
+    pub min_balance: u64,
+    // Claim amount multiplier, 10_000 keeps the amount as is
+    pub amount_bps: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace, Debug)]
+pub struct AirdropEligibility {
+    pub required_mint: Pubkey,
+    // In base units of the required mint
+    pub min_balance: u64,
+    // Registration deadline, holders register their balance up to this slot with
+    // register_for_airdrop and only registered holders can claim, counting the lowest
+    // of the registered and the current balance. This is not a sybil resistant snapshot,
+    // see AirdropRegistration
+    pub registration_deadline_slot: Option<u64>,
+    // Sorted by min_balance, the highest tier reached scales the claim amount
+    #[max_len(AIRDROP_MAX_BALANCE_TIERS)]
+    pub tiers: Vec<AirdropBalanceTier>,
+}
+
+impl AirdropEligibility {
+    pub fn amount_bps(&self, balance: u64) -> u64 {
+        self.tiers
+            .iter()
+            .rev()
+            .find(|tier| balance >= tier.min_balance)
+            .map_or(10_000, |tier| tier.amount_bps)
+    }
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct Airdrop {
@@ -11,4 +65,12 @@ pub struct Airdrop {
     pub amount_per_claim: u64,
     pub start_datetime: i64,
     pub end_datetime: i64,
+    pub mode: AirdropMode,
+    // Claims open an AirdropVestingPosition instead of paying out right away
+    pub vesting: Option<AirdropVesting>,
+    // Claims are rejected while paused, vested withdrawals keep working
+    pub is_paused: bool,
+    pub is_cancelled: bool,
+    // Claims are restricted to holders of the required mint
+    pub eligibility: Option<AirdropEligibility>,
 }
\ No newline at end of file
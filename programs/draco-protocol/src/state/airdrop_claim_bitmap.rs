@@ -0,0 +1,31 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::AIRDROP_CLAIM_BITMAP_WORDS;
+
+// One bit per Merkle leaf index, split in chunks of AIRDROP_CLAIM_BITMAP_BITS indexes
+#[account]
+#[derive(InitSpace)]
+pub struct AirdropClaimBitmap {
+    pub airdrop_id: u64,
+    pub chunk: u64,
+    // Paid the rent of the chunk, it gets it back when the chunk is closed
+    pub rent_payer: Pubkey,
+    pub claimed: [u64; AIRDROP_CLAIM_BITMAP_WORDS],
+}
+
+impl AirdropClaimBitmap {
+    fn position(index: u64) -> (usize, u64) {
+        let bit = index as usize % (AIRDROP_CLAIM_BITMAP_WORDS * 64);
+        (bit / 64, 1 << (bit % 64))
+    }
+
+    pub fn is_claimed(&self, index: u64) -> bool {
+        let (word, mask) = Self::position(index);
+        self.claimed[word] & mask != 0
+    }
+
+    pub fn set_claimed(&mut self, index: u64) {
+        let (word, mask) = Self::position(index);
+        self.claimed[word] |= mask;
+    }
+}
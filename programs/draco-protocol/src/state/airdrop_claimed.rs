@@ -5,4 +5,6 @@ use anchor_lang::prelude::*;
 pub struct AirdropClaimed {
     pub airdrop_id: u64,
     pub claimer: Pubkey,
+    // Paid the rent of the claim record, e.g. a relayer, it gets it back when the record is closed
+    pub rent_payer: Pubkey,
 }
\ No newline at end of file
@@ -0,0 +1,15 @@
+use anchor_lang::prelude::*;
+
+// Eligibility balance a holder registered before the registration deadline of an airdrop.
+// Registration is opt-in and reads the balance at registration time, so it is not a
+// snapshot: holders who did not register are not eligible and a balance moved between
+// wallets can register from each of them. Airdrops needing a point in time snapshot
+// should take it off-chain and distribute through a merkle root instead.
+#[account]
+#[derive(InitSpace)]
+pub struct AirdropRegistration {
+    pub airdrop_id: u64,
+    pub holder: Pubkey,
+    pub balance: u64,
+    pub slot: u64,
+}
@@ -0,0 +1,9 @@
+use anchor_lang::prelude::*;
+
+// Left behind when an airdrop is closed, so its id can't be reused
+#[account]
+#[derive(InitSpace)]
+pub struct AirdropTombstone {
+    pub airdrop_id: u64,
+    pub closed_at: i64,
+}
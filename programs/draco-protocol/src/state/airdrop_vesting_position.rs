@@ -0,0 +1,31 @@
+use anchor_lang::prelude::*;
+
+#[account]
+#[derive(InitSpace)]
+pub struct AirdropVestingPosition {
+    pub airdrop_id: u64,
+    pub claimer: Pubkey,
+    // Amounts are in token base units so partial releases aren't rounded to whole tokens
+    pub total_amount: u64,
+    pub withdrawn_amount: u64,
+    pub start_datetime: i64,
+    pub cliff_datetime: i64,
+    pub end_datetime: i64,
+    pub is_initialized: bool,
+}
+
+impl AirdropVestingPosition {
+    /// Nothing is unlocked before the cliff, then the amount unlocks linearly
+    /// from `start_datetime` until `end_datetime`.
+    pub fn vested_amount(&self, now: i64) -> u64 {
+        if now < self.cliff_datetime {
+            return 0;
+        }
+        if now >= self.end_datetime {
+            return self.total_amount;
+        }
+        let elapsed = (now - self.start_datetime) as u128;
+        let duration = (self.end_datetime - self.start_datetime) as u128;
+        (self.total_amount as u128 * elapsed / duration) as u64
+    }
+}
@@ -1,5 +1,26 @@
 use anchor_lang::prelude::*;
 
+use crate::state::protocol_error::ErrorCode;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum LotteryStatus {
+    // Created, waiting for lottery_start_datetime
+    Scheduled,
+    // Tickets can be bought
+    Open,
+    // Ticket sales are over, waiting for the randomness commit
+    SalesClosed,
+    RandomnessCommitted,
+    // Winning combination revealed, no prize claimed yet
+    Drawn,
+    // At least one prize has been claimed
+    Claiming,
+    Closed,
+    Cancelled,
+    // At least one ticket of a cancelled lottery has been refunded
+    Refunding,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct LotteryState {
@@ -11,15 +32,133 @@ pub struct LotteryState {
     pub lottery_type: u8,
     pub lottery_start_datetime: i64,
     pub lottery_end_datetime: i64,
-    pub is_closed: bool,
+    // Ticket sales stop this many seconds before lottery_end_datetime
+    pub sales_close_buffer: i64,
+    // Slot on which the lottery was first seen past its sales close datetime, not the
+    // close datetime itself. close_lottery_sales has to be cranked before the randomness
+    // is seeded, or the commit fails with RandomnessSeededBeforeSalesClosed.
+    pub sales_closed_slot: u64,
+    pub status: LotteryStatus,
     pub initial_prize_pool: u64,
     pub accumulated_prize_pool: u64,
+    // Tokens added to the vault by third party sponsors, see Sponsorship
+    pub sponsored_prize_pool: u64,
+    // Distinct wallets that bought at least one ticket
     pub participants_count: u64,
+    pub max_participants: Option<u64>,
+    // Ticket units per wallet, see ticket_units
+    pub max_tickets_per_wallet: Option<u64>,
+    // Tickets are minted as Token-2022 NFTs and prizes go to whoever holds them
+    pub ticket_nft_mode: bool,
     // If lottery type is PAY_LOTTERY_TYPE, this is ticket price
     // If lottery type is LOCK_LOTTERY_TYPE, this is the minimum amount of tokens to lock the ticker
     pub min_tokens_per_participant: u64,
     #[max_len(8)]
     pub winning_combination: Option<String>,
     pub randomness_account: Pubkey,
+    // When the winning combination was revealed, 0 until then
+    pub drawn_datetime: i64,
+    // Prizes can't be claimed after this datetime
+    pub claim_deadline: i64,
+    // Prize pool never paid out when the claim window expired, in DRACO. LOCK principal
+    // isn't part of the prize pool, and sponsorships only count for what wasn't paid out.
+    pub expired_unclaimed_amount: Option<u64>,
+    // Prizes settled so far, in DRACO
+    pub claimed_prize_amount: u64,
+    // Running sha256 over every (participant, combination, amount) purchase and every
+    // ("transfer", seller, buyer, combination, amount) marketplace sale, frozen by
+    // finalize_ticket_set before the randomness can be committed
+    pub ticket_set_hash: [u8; 32],
+    pub ticket_set_entries: u64,
+    pub ticket_set_finalized: bool,
+}
+
+impl LotteryState {
+    /// Moves the lottery to `next`, failing with the error that best describes
+    /// why the lottery can't go there from its current status.
+    pub fn transition(&mut self, next: LotteryStatus) -> Result<()> {
+        use LotteryStatus::*;
+
+        let legal = matches!(
+            (self.status, next),
+            (Scheduled, Open)
+                | (Open, SalesClosed)
+                | (SalesClosed, RandomnessCommitted)
+                | (RandomnessCommitted, Drawn)
+                | (Drawn, Claiming)
+                | (Claiming, Claiming)
+                | (Drawn, Closed)
+                | (Claiming, Closed)
+                | (Scheduled, Cancelled)
+                | (Open, Cancelled)
+                | (SalesClosed, Cancelled)
+                | (RandomnessCommitted, Cancelled)
+                | (Cancelled, Refunding)
+                | (Refunding, Refunding)
+        );
+        if legal {
+            msg!("Lottery {} status {:?} -> {:?}", self.lottery_id, self.status, next);
+            self.status = next;
+            return Ok(());
+        }
+
+        let error = match (self.status, next) {
+            (_, Refunding) => ErrorCode::LotteryNotCancelled,
+            (Closed, _) => ErrorCode::LotteryClosed,
+            (Cancelled | Refunding, _) => ErrorCode::LotteryCancelled,
+            (Scheduled, _) => ErrorCode::LotteryNotStarted,
+            (Open, RandomnessCommitted) => ErrorCode::CantCommitOnNotFinishedLottery,
+            (Open, Drawn) => ErrorCode::CantRevealOnNotFinishedLottery,
+            (Open, _) => ErrorCode::LotteryNotFinished,
+            (SalesClosed, Drawn) => ErrorCode::RandomnessNotCommitted,
+            (_, Open) => ErrorCode::LotteryFinished,
+            (RandomnessCommitted, RandomnessCommitted) => ErrorCode::RandomnessAlreadyCommitted,
+            (RandomnessCommitted, _) => ErrorCode::WinningCombinationNotSetYet,
+            (Drawn | Claiming, RandomnessCommitted | Drawn) => ErrorCode::CombinationAlreadySet,
+            (Drawn | Claiming, Cancelled) => ErrorCode::CantCancelDrawnLottery,
+            (SalesClosed, _) => ErrorCode::WinningCombinationNotSetYet,
+            _ => ErrorCode::InvalidLotteryStatusTransition,
+        };
+        msg!("Lottery {} can't go from {:?} to {:?}", self.lottery_id, self.status, next);
+        Err(error.into())
+    }
+
+    /// Ticket units held by a ticket of `amount`, the per wallet cap counts these
+    /// so topping up a combination counts the same as buying it again.
+    pub fn ticket_units(&self, amount: u64) -> u64 {
+        amount / self.min_tokens_per_participant
+    }
+
+    pub fn sales_close_datetime(&self) -> i64 {
+        self.lottery_end_datetime.saturating_sub(self.sales_close_buffer)
+    }
+
+    /// Applies the transitions driven purely by the clock: the lottery opens at
+    /// `lottery_start_datetime` and stops selling at its sales close datetime.
+    pub fn sync_status(&mut self, clock: &Clock) -> Result<()> {
+        if self.status == LotteryStatus::Scheduled && self.lottery_start_datetime < clock.unix_timestamp {
+            self.transition(LotteryStatus::Open)?;
+        }
+        if self.status == LotteryStatus::Open && self.sales_close_datetime() <= clock.unix_timestamp {
+            self.close_sales(clock)?;
+        }
+        Ok(())
+    }
+
+    pub fn close_sales(&mut self, clock: &Clock) -> Result<()> {
+        self.transition(LotteryStatus::SalesClosed)?;
+        self.sales_closed_slot = clock.slot;
+        Ok(())
+    }
+
+    pub fn require_open(&self) -> Result<()> {
+        let error = match self.status {
+            LotteryStatus::Open => return Ok(()),
+            LotteryStatus::Scheduled => ErrorCode::LotteryNotStarted,
+            LotteryStatus::Closed => ErrorCode::LotteryClosed,
+            LotteryStatus::Cancelled | LotteryStatus::Refunding => ErrorCode::LotteryCancelled,
+            _ => ErrorCode::LotteryFinished,
+        };
+        Err(error.into())
+    }
 }
-    
\ No newline at end of file
@@ -0,0 +1,9 @@
+use anchor_lang::prelude::*;
+
+// Left behind when the accounts of a lottery are closed, so its id can't be reused
+#[account]
+#[derive(InitSpace)]
+pub struct LotteryTombstone {
+    pub lottery_id: u64,
+    pub closed_at: i64,
+}
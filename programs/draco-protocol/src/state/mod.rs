@@ -7,8 +7,6 @@ pub use protocol_authority::*;
 pub mod lottery_reward_factors;
 pub use lottery_reward_factors::*;
 
-pub mod six_month_cliff;
-pub use six_month_cliff::*;
 
 pub mod lottery;
 pub use lottery::*;
@@ -16,8 +14,41 @@ pub use lottery::*;
 pub mod ticket;
 pub use ticket::*;
 
+pub mod participant;
+pub use participant::*;
+
+pub mod lottery_tombstone;
+pub use lottery_tombstone::*;
+
 pub mod airdrop;
 pub use airdrop::*;
 
 pub mod airdrop_claimed;
-pub use airdrop_claimed::*;
\ No newline at end of file
+pub use airdrop_claimed::*;
+
+pub mod airdrop_claim_bitmap;
+pub use airdrop_claim_bitmap::*;
+
+pub mod airdrop_vesting_position;
+pub use airdrop_vesting_position::*;
+
+pub mod airdrop_registration;
+pub use airdrop_registration::*;
+
+pub mod airdrop_tombstone;
+pub use airdrop_tombstone::*;
+
+pub mod sponsorship;
+pub use sponsorship::*;
+
+pub mod ticket_listing;
+pub use ticket_listing::*;
+
+pub mod syndicate;
+pub use syndicate::*;
+
+pub mod payout_config;
+pub use payout_config::*;
+
+pub mod vesting_schedule;
+pub use vesting_schedule::*;
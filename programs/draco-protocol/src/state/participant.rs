@@ -0,0 +1,14 @@
+use anchor_lang::prelude::*;
+
+#[account]
+#[derive(InitSpace)]
+pub struct Participant {
+    pub lottery_id: u64,
+    pub wallet: Pubkey,
+    // Paid the participant rent, e.g. a relayer, it gets it back when the participant is closed
+    pub rent_payer: Pubkey,
+    // Ticket units the wallet holds in the lottery, see LotteryState::ticket_units
+    pub tickets_count: u64,
+    pub total_spent: u64,
+    pub is_initialized: bool,
+}
@@ -0,0 +1,8 @@
+use anchor_lang::prelude::*;
+
+#[account]
+#[derive(InitSpace)]
+pub struct PayoutConfig {
+    // DRACO paid from the treasury to whoever cranks a winning ticket payout
+    pub payout_bounty: u64,
+}
@@ -91,4 +91,181 @@ pub enum ErrorCode {
 
     #[msg("Airdrop supply exhausted")]
     AirdropSupplyExhausted,
+
+    #[msg("Lottery is cancelled")]
+    LotteryCancelled,
+
+    #[msg("Randomness not committed yet")]
+    RandomnessNotCommitted,
+
+    #[msg("Randomness already committed")]
+    RandomnessAlreadyCommitted,
+
+    #[msg("Invalid lottery status transition")]
+    InvalidLotteryStatusTransition,
+
+    #[msg("Lottery can't be cancelled once the winning combination is drawn")]
+    CantCancelDrawnLottery,
+
+    #[msg("Lottery is not cancelled")]
+    LotteryNotCancelled,
+
+    #[msg("Lottery has already started")]
+    LotteryAlreadyStarted,
+
+    #[msg("Lottery already has tickets sold")]
+    LotteryTicketsAlreadySold,
+
+    #[msg("Lottery end datetime can only be extended once the lottery started")]
+    CantShortenStartedLottery,
+
+    #[msg("Lottery can't be sponsored once the randomness is committed")]
+    LotterySponsorshipClosed,
+
+    #[msg("Ticket can only be closed once claimed or once the lottery is closed")]
+    TicketNotClosable,
+
+    #[msg("Lottery is not closed")]
+    LotteryNotClosed,
+
+    #[msg("Lottery token account still holds tokens")]
+    LotteryVaultNotEmpty,
+
+    #[msg("Airdrop has not ended yet")]
+    AirdropNotEnded,
+
+    #[msg("Lottery limits must be greater than zero when set")]
+    InvalidLotteryLimits,
+
+    #[msg("Lottery reached its maximum number of participants")]
+    MaxParticipantsReached,
+
+    #[msg("Wallet reached the maximum number of tickets for this lottery")]
+    MaxTicketsPerWalletReached,
+
+    #[msg("Sales close buffer must leave a non empty sales window")]
+    InvalidSalesCloseBuffer,
+
+    #[msg("Lottery sales are not closed yet")]
+    LotterySalesNotClosed,
+
+    #[msg("Randomness must be seeded after the lottery sales closed")]
+    RandomnessSeededBeforeSalesClosed,
+
+    #[msg("Lottery ticket set is not finalized yet")]
+    TicketSetNotFinalized,
+
+    #[msg("Lottery ticket set already finalized")]
+    TicketSetAlreadyFinalized,
+
+    #[msg("Ticket NFT accounts must be provided if and only if the lottery is in ticket NFT mode")]
+    TicketNftModeMismatch,
+
+    #[msg("Signer does not hold the ticket NFT")]
+    NotTicketHolder,
+
+    #[msg("Signer does not own the ticket")]
+    NotTicketOwner,
+
+    #[msg("Tickets of lotteries in ticket NFT mode are traded as NFTs")]
+    TicketNotListable,
+
+    #[msg("Ticket listing expired")]
+    TicketListingExpired,
+
+    #[msg("Only the seller can delist a ticket before its listing expires")]
+    TicketListingNotExpired,
+
+    #[msg("Only the syndicate manager can buy tickets for the syndicate")]
+    NotSyndicateManager,
+
+    #[msg("Syndicate contributions are closed")]
+    SyndicateContributionsClosed,
+
+    #[msg("Syndicate shares can't be withdrawn until the lottery is drawn or cancelled")]
+    SyndicateWithdrawalsNotOpen,
+
+    #[msg("Nothing to withdraw")]
+    NothingToWithdraw,
+
+    #[msg("Syndicates can't join lotteries in ticket NFT mode")]
+    SyndicateNftModeNotSupported,
+
+    #[msg("Ticket can't be paid out by the crank, it must be claimed")]
+    TicketNotPayable,
+
+    #[msg("Tickets of lotteries in ticket NFT mode must be claimed one by one")]
+    TicketNftBatchClaim,
+
+    #[msg("Claim window expired")]
+    ClaimWindowExpired,
+
+    #[msg("Claim window has not expired yet")]
+    ClaimWindowNotExpired,
+
+    #[msg("Unclaimed prizes already expired")]
+    UnclaimedPrizesAlreadyExpired,
+
+    #[msg("Claim deadline can only be pushed back")]
+    InvalidClaimDeadline,
+
+    #[msg("Claim proof and accounts don't match the airdrop mode")]
+    AirdropModeMismatch,
+
+    #[msg("Invalid Merkle proof")]
+    InvalidMerkleProof,
+
+    #[msg("Airdrop already claimed")]
+    AirdropAlreadyClaimed,
+
+    #[msg("Airdrop claim signature is missing or invalid")]
+    InvalidClaimSignature,
+
+    #[msg("Airdrop claim signature expired")]
+    ClaimSignatureExpired,
+
+    #[msg("Vesting cliff must be within a positive vesting duration")]
+    InvalidAirdropVesting,
+
+    #[msg("Airdrop claims are paused")]
+    AirdropPaused,
+
+    #[msg("Airdrop has been cancelled")]
+    AirdropCancelled,
+
+    #[msg("Airdrop eligibility rules are invalid")]
+    InvalidAirdropEligibility,
+
+    #[msg("Claimer doesn't hold enough of the required mint")]
+    NotEligibleForAirdrop,
+
+    #[msg("Airdrop registration deadline has passed")]
+    AirdropRegistrationClosed,
+
+    #[msg("Vesting schedule parameters are invalid")]
+    InvalidVestingSchedule,
+
+    #[msg("Vesting schedule is not revocable")]
+    VestingScheduleNotRevocable,
+
+    #[msg("Vesting schedule has already been revoked")]
+    VestingScheduleRevoked,
+
+    #[msg("Lottery id has already been used")]
+    LotteryIdAlreadyUsed,
+
+    #[msg("Airdrop id has already been used")]
+    AirdropIdAlreadyUsed,
+
+    #[msg("Syndicate tickets have to be refunded to their syndicate")]
+    TicketNotRefundable,
+
+    #[msg("Payout bounty is above the maximum")]
+    InvalidPayoutBounty,
+
+    #[msg("Claim deadline must be after the lottery end datetime")]
+    ClaimDeadlineBeforeLotteryEnd,
+
+    #[msg("Airdrop vesting position ended, withdraw it before claiming again")]
+    AirdropVestingPositionEnded,
 }
\ No newline at end of file
@@ -0,0 +1,9 @@
+use anchor_lang::prelude::*;
+
+#[account]
+#[derive(InitSpace)]
+pub struct Sponsorship {
+    pub lottery_id: u64,
+    pub sponsor: Pubkey,
+    pub amount: u64,
+}
@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+#[account]
+#[derive(InitSpace)]
+pub struct Syndicate {
+    pub syndicate_id: u64,
+    pub lottery_id: u64,
+    // Only the manager buys tickets with the pooled tokens
+    pub manager: Pubkey,
+    pub members_count: u64,
+    pub total_contributed: u64,
+    // Scaled tokens already withdrawn by members, so shares can be computed
+    // against everything the syndicate vault ever held
+    pub total_withdrawn: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct SyndicateMember {
+    pub syndicate_id: u64,
+    pub member: Pubkey,
+    pub contributed: u64,
+    // Scaled tokens withdrawn so far
+    pub withdrawn: u64,
+    pub is_initialized: bool,
+}
@@ -6,7 +6,12 @@ pub struct Ticket {
     pub lottery_id: u64,
     #[max_len(8)]
     pub combination: String,
+    // Current owner of the ticket, rewritten when the ticket is sold on the marketplace
     pub participant: Pubkey,
+    // Wallet that bought the ticket, the ticket PDA is derived from it
+    pub original_participant: Pubkey,
+    // Paid the ticket rent, e.g. a relayer, it gets it back when the ticket is closed
+    pub rent_payer: Pubkey,
     pub amount: u64,
     pub is_claimed: bool,
     pub is_initialized: bool,
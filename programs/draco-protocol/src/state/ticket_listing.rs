@@ -0,0 +1,13 @@
+use anchor_lang::prelude::*;
+
+#[account]
+#[derive(InitSpace)]
+pub struct TicketListing {
+    pub lottery_id: u64,
+    pub ticket: Pubkey,
+    pub seller: Pubkey,
+    // Price in DRACO, the protocol fee is taken out of it on sale
+    pub price: u64,
+    // Sales close datetime of the lottery, the listing can't be bought after it
+    pub expires_at: i64,
+}
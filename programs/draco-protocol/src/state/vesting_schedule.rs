@@ -0,0 +1,56 @@
+use anchor_lang::prelude::*;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum VestingRelease {
+    // Unlocks continuously from start_datetime until end_datetime
+    Linear { end_datetime: i64 },
+    // Unlocks amount_per_period (in base units) every period seconds after start_datetime
+    Periodic { period: i64, amount_per_period: u64 },
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct VestingSchedule {
+    pub schedule_id: u64,
+    // Signer that created the schedule, part of its seeds
+    pub creator: Pubkey,
+    pub beneficiary: Pubkey,
+    // Funded from the treasury by the authority, otherwise from the creator's own tokens
+    pub funded_from_treasury: bool,
+    // Amounts are in token base units
+    pub total_amount: u64,
+    pub released_amount: u64,
+    pub start_datetime: i64,
+    // Nothing is released before this datetime, even if periods already elapsed
+    pub cliff_datetime: i64,
+    pub release: VestingRelease,
+    // The authority can stop a revocable schedule, see revoke_vesting_schedule
+    pub revocable: bool,
+    // Vesting stops at this datetime, what was vested by then stays releasable
+    pub revoked_at: Option<i64>,
+    // Unvested amount returned to the treasury on revocation, in base units
+    pub clawed_back_amount: u64,
+}
+
+impl VestingSchedule {
+    pub fn vested_amount(&self, now: i64) -> u64 {
+        let now = self.revoked_at.map_or(now, |revoked_at| now.min(revoked_at));
+        if now < self.cliff_datetime {
+            return 0;
+        }
+        let elapsed = (now - self.start_datetime).max(0);
+        match self.release {
+            VestingRelease::Linear { end_datetime } => {
+                if now >= end_datetime {
+                    return self.total_amount;
+                }
+                let duration = (end_datetime - self.start_datetime) as u128;
+                (self.total_amount as u128 * elapsed as u128 / duration) as u64
+            }
+            VestingRelease::Periodic { period, amount_per_period } => {
+                let periods = (elapsed / period) as u64;
+                periods.saturating_mul(amount_per_period).min(self.total_amount)
+            }
+        }
+    }
+}
@@ -1,11 +1,19 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { DracoProtocol } from "../target/types/draco_protocol";
-import { Commitment, Keypair, PublicKey } from "@solana/web3.js";
-import { TOKEN_2022_PROGRAM_ID } from "@solana/spl-token";
+import { Commitment, Ed25519Program, Keypair, PublicKey, SYSVAR_INSTRUCTIONS_PUBKEY } from "@solana/web3.js";
+import {
+  TOKEN_2022_PROGRAM_ID,
+  getAssociatedTokenAddressSync,
+  getMint,
+  getOrCreateAssociatedTokenAccount,
+  transferChecked,
+} from "@solana/spl-token";
 import * as dotenv from "dotenv";
 import * as sb from "@switchboard-xyz/on-demand";
 import bs58 from "bs58";
+import { assert } from "chai";
+import { createHash } from "crypto";
 // Load environment variables from .env file
 dotenv.config();
 
@@ -17,8 +25,31 @@ if (!TOKEN_MINT) {
 const FINISHED_LOTTERY_ID = new anchor.BN(1);
 const PAY_LOTTERY_ID = new anchor.BN(2);
 const LOCK_LOTTERY_ID = new anchor.BN(3);
+const CANCELLED_LOTTERY_ID = new anchor.BN(4);
+const SCHEDULED_LOTTERY_ID = new anchor.BN(5);
+const NFT_LOTTERY_ID = new anchor.BN(6);
+const MARKETPLACE_LOTTERY_ID = new anchor.BN(7);
+const CAPPED_LOTTERY_ID = new anchor.BN(8);
+const AIRDROP_LOTTERY_ID = new anchor.BN(9);
+const SYNDICATE_LOTTERY_ID = new anchor.BN(10);
+const SYNDICATE_ID = new anchor.BN(1);
+const DRAWN_SYNDICATE_ID = new anchor.BN(2);
+const REFUNDED_SYNDICATE_ID = new anchor.BN(3);
+const VESTING_SCHEDULE_ID = new anchor.BN(1);
+const MERKLE_AIRDROP_ID = new anchor.BN(1);
+const SIGNED_AIRDROP_ID = new anchor.BN(2);
+const TIERED_AIRDROP_ID = new anchor.BN(3);
+const TICKET_AIRDROP_ID = new anchor.BN(4);
+const VESTING_AIRDROP_ID = new anchor.BN(5);
+const CLOSED_AIRDROP_ID = new anchor.BN(6);
+const MANAGED_AIRDROP_ID = new anchor.BN(7);
+const ENDED_AIRDROP_ID = new anchor.BN(8);
 const WRONG_COMBINATION = "W2H10C8CA";
 const VALID_COMBINATION = "W2HTC8CA";
+const PAYOUT_COMBINATION = "SAHKCQWJ";
+const SELF_PAYOUT_COMBINATION = "S3H4C5W6";
+const SYNDICATE_COMBINATION = "S9S8S7S6";
+const BATCH_COMBINATIONS = ["C2C3C4C5", "H2H3H4H5"];
 const WRONG_AMOUNT_PAY = new anchor.BN(40);
 const VALID_AMOUNT_PAY = new anchor.BN(50);
 const WRONG_AMOUNT_LOCK = new anchor.BN(400);
@@ -82,6 +113,197 @@ describe("- Draco Protocol Set Up", () => {
 
   const rngKp = anchor.web3.Keypair.generate();
 
+  const ticketPDA = (lotteryId: anchor.BN, participant: PublicKey, combination: string) => PublicKey.findProgramAddressSync(
+    [
+      Buffer.from("ticket"),
+      lotteryId.toArrayLike(Buffer, "le", 8),
+      participant.toBuffer(),
+      Buffer.from(combination),
+    ],
+    program.programId
+  )[0];
+
+  const participantPDA = (lotteryId: anchor.BN, wallet: PublicKey) => PublicKey.findProgramAddressSync(
+    [
+      Buffer.from("participant"),
+      lotteryId.toArrayLike(Buffer, "le", 8),
+      wallet.toBuffer(),
+    ],
+    program.programId
+  )[0];
+
+  // Optional settings of startLottery, see LotteryConfig
+  const lotteryConfig = (overrides: object = {}) => ({
+    maxParticipants: null,
+    maxTicketsPerWallet: null,
+    salesCloseBuffer: new anchor.BN(0),
+    ticketNftMode: false,
+    claimDeadline: null,
+    ...overrides,
+  });
+
+  const expectError = async (tx: Promise<unknown>, errorCode: string) => {
+    try {
+      await tx;
+    } catch (e) {
+      assert.include(String(e) + JSON.stringify(e.logs ?? []), errorCode);
+      return;
+    }
+    assert.fail(`Expected ${errorCode}`);
+  };
+
+  const airdropPDA = (seed: string, airdropId: anchor.BN, ...keys: Buffer[]) => PublicKey.findProgramAddressSync(
+    [Buffer.from(seed), airdropId.toArrayLike(Buffer, "le", 8), ...keys],
+    program.programId
+  )[0];
+
+  const [treasuryPDA] = PublicKey.findProgramAddressSync([Buffer.from("treasury")], program.programId);
+
+  // Balance of a program owned token account, e.g. the treasury or a vault
+  const vaultBalance = async (tokenAccount: PublicKey) => BigInt(
+    (await connection.getTokenAccountBalance(tokenAccount)).value.amount
+  );
+
+  const tokenBalance = async (owner: PublicKey) => BigInt(
+    (await connection.getTokenAccountBalance(
+      getAssociatedTokenAddressSync(TOKEN_MINT, owner, false, TOKEN_2022_PROGRAM_ID)
+    )).value.amount
+  );
+
+  // Fresh wallet with SOL for fees and `draco` DRACO taken from the payer
+  const fundedWallet = async (draco: number) => {
+    const wallet = Keypair.generate();
+    await connection.confirmTransaction(
+      await connection.requestAirdrop(wallet.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+    );
+    const mint = await getMint(connection, TOKEN_MINT, undefined, TOKEN_2022_PROGRAM_ID);
+    const walletTokenAccount = await getOrCreateAssociatedTokenAccount(
+      connection, signer, TOKEN_MINT, wallet.publicKey, false, undefined, undefined, TOKEN_2022_PROGRAM_ID
+    );
+    await transferChecked(
+      connection,
+      signer,
+      getAssociatedTokenAddressSync(TOKEN_MINT, payer, false, TOKEN_2022_PROGRAM_ID),
+      TOKEN_MINT,
+      walletTokenAccount.address,
+      signer,
+      BigInt(draco) * BigInt(10) ** BigInt(mint.decimals),
+      mint.decimals,
+      [],
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    return wallet;
+  };
+
+  const scaled = async (draco: bigint) =>
+    draco * BigInt(10) ** BigInt((await getMint(connection, TOKEN_MINT, undefined, TOKEN_2022_PROGRAM_ID)).decimals);
+
+  const syndicatePDA = (syndicateId: anchor.BN) => PublicKey.findProgramAddressSync(
+    [Buffer.from("syndicate"), syndicateId.toArrayLike(Buffer, "le", 8)],
+    program.programId
+  )[0];
+
+  const syndicateVaultPDA = (syndicateId: anchor.BN) => PublicKey.findProgramAddressSync(
+    [Buffer.from("syndicate_token_account"), syndicateId.toArrayLike(Buffer, "le", 8)],
+    program.programId
+  )[0];
+
+  const contributeToSyndicate = (syndicateId: anchor.BN, member: Keypair, amount: number) => program.methods
+    .contributeToSyndicate(syndicateId, new anchor.BN(amount))
+    .accounts({
+      payer: member.publicKey,
+      tokenMint: TOKEN_MINT,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+    })
+    .signers([member])
+    .rpc({ skipPreflight: true });
+
+  const withdrawSyndicateShare = (syndicateId: anchor.BN, member: Keypair) => program.methods
+    .withdrawSyndicateShare(syndicateId)
+    .accounts({
+      payer: member.publicKey,
+      tokenMint: TOKEN_MINT,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+    })
+    .signers([member])
+    .rpc({ skipPreflight: true });
+
+  // Same hashing as verify_merkle_proof: domain separated leaves and sorted pairs
+  const sha256 = (...parts: Buffer[]) => createHash("sha256").update(Buffer.concat(parts)).digest();
+  const merkleLeaf = (index: number, claimer: PublicKey, amount: number) => sha256(
+    Buffer.from([0]),
+    new anchor.BN(index).toArrayLike(Buffer, "le", 8),
+    claimer.toBuffer(),
+    new anchor.BN(amount).toArrayLike(Buffer, "le", 8),
+  );
+  const merkleNode = (a: Buffer, b: Buffer) => Buffer.compare(a, b) <= 0
+    ? sha256(Buffer.from([1]), a, b)
+    : sha256(Buffer.from([1]), b, a);
+
+  // Message the airdrop signer approves a Signed mode claim with, see SignedClaim
+  const claimSignature = (signer: Keypair, airdropId: anchor.BN, claimer: PublicKey, amount: anchor.BN, expiry: anchor.BN) =>
+    Ed25519Program.createInstructionWithPrivateKey({
+      privateKey: signer.secretKey,
+      message: Buffer.concat([
+        Buffer.from("draco_protocol:claim_airdrop"),
+        program.programId.toBuffer(),
+        airdropId.toArrayLike(Buffer, "le", 8),
+        claimer.toBuffer(),
+        amount.toArrayLike(Buffer, "le", 8),
+        expiry.toArrayLike(Buffer, "le", 8),
+      ]),
+    });
+
+  const createAirdrop = (airdropId: anchor.BN, supply: number, config: any, duration: number = 60*60) => {
+    const now = Math.floor(Date.now() / 1000);
+    return program.methods
+      .createAirdrop(
+        airdropId,
+        "Test Airdrop",
+        new anchor.BN(supply),
+        new anchor.BN(now - 60),
+        new anchor.BN(now + duration),
+        config,
+      )
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+  };
+
+  // Merkle claims track the leaf in the first bitmap chunk, the other modes use a claim record
+  const claimAirdrop = (
+    airdropId: anchor.BN,
+    user: Keypair,
+    merkleClaim: { index: anchor.BN, amount: anchor.BN, proof: number[][] } | null,
+    signedClaim: { amount: anchor.BN, expiry: anchor.BN } | null,
+    eligibilityTokenAccount: PublicKey | null = null,
+    vesting: boolean = false,
+  ) => program.methods
+    .claimAirdrop(airdropId, merkleClaim, signedClaim)
+    .accountsPartial({
+      feePayer: payer,
+      user: user.publicKey,
+      userTokenAccount: vesting
+        ? null
+        : getAssociatedTokenAddressSync(TOKEN_MINT, user.publicKey, false, TOKEN_2022_PROGRAM_ID),
+      airdropClaimed: merkleClaim ? null : airdropPDA("airdrop_claimed", airdropId, user.publicKey.toBuffer()),
+      airdropClaimBitmap: merkleClaim
+        ? airdropPDA("airdrop_claim_bitmap", airdropId, new anchor.BN(0).toArrayLike(Buffer, "le", 8))
+        : null,
+      instructionsSysvar: signedClaim ? SYSVAR_INSTRUCTIONS_PUBKEY : null,
+      airdropVestingPosition: vesting ? airdropPDA("airdrop_vesting_position", airdropId, user.publicKey.toBuffer()) : null,
+      eligibilityTokenAccount: eligibilityTokenAccount,
+      airdropRegistration: null,
+      tokenMint: TOKEN_MINT,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+    })
+    .signers(user === signer ? [signer] : [signer, user]);
+
 
   it("Initializing authority", async () => {
     try {
@@ -116,6 +338,15 @@ describe("- Draco Protocol Set Up", () => {
       })
       .signers([signer])
       .rpc({ skipPreflight: true });
+
+    // The former six month cliff is a vesting schedule of the authority
+    const [treasuryVestingSchedule] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vesting_schedule"), payer.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const vestingSchedule = await program.account.vestingSchedule.fetch(treasuryVestingSchedule);
+    assert.isTrue(vestingSchedule.beneficiary.equals(payer));
+    assert.isFalse(vestingSchedule.revocable);
   })
 
   it("Initialize Lottery Reward Factors", async () => {
@@ -128,6 +359,30 @@ describe("- Draco Protocol Set Up", () => {
       .rpc({ skipPreflight: true });
   })
 
+  it("Set payout bounty", async () => {
+    const txSig = await program.methods
+      .setPayoutBounty(new anchor.BN(1))
+      .accounts({
+        payer: payer,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    const [payoutConfig] = PublicKey.findProgramAddressSync([Buffer.from("payout_config")], program.programId);
+    assert.equal((await program.account.payoutConfig.fetch(payoutConfig)).payoutBounty.toNumber(), 1);
+
+    await expectError(
+      program.methods
+        .setPayoutBounty(new anchor.BN(101))
+        .accounts({
+          payer: payer,
+        })
+        .signers([signer])
+        .rpc({ skipPreflight: true }),
+      "InvalidPayoutBounty"
+    );
+  })
+
   it("Update Lottery Reward Factors", async () => {
     const txSig = await program.methods.updateLotteryRewardFactors(
       1.0,
@@ -147,22 +402,56 @@ describe("- Draco Protocol Set Up", () => {
     .rpc({ skipPreflight: true });
   })
 
-  it("Transfer out from six month cliff", async () => {
-    try{
-      const txSig = await program.methods
-      .transferOutFromSixMonthCliff()
-      .accounts({
-        payer: payer,
-        tokenMint: TOKEN_MINT,
-        tokenProgram: TOKEN_2022_PROGRAM_ID,
-      })
-      .signers([signer])
-      .rpc({ skipPreflight: true });
-    }
-    catch (e) {
-      console.log("Six month cliff:");
-      console.log(e.msg);
-    }
+  it("Create, release and revoke a vesting schedule", async () => {
+    const now = Math.floor(Date.now() / 1000);
+    await program.methods
+    .createVestingSchedule(
+      VESTING_SCHEDULE_ID,
+      {
+        beneficiary: payer,
+        totalAmount: new anchor.BN(1000),
+        startDatetime: new anchor.BN(now - 60),
+        cliffDatetime: new anchor.BN(now - 60),
+        release: { periodic: { period: new anchor.BN(30), amountPerPeriod: new anchor.BN(100) } },
+        fromTreasury: true,
+        revocable: true,
+      },
+    )
+    .accounts({
+      payer: payer,
+      payerTokenAccount: null,
+      tokenMint: TOKEN_MINT,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+    })
+    .signers([signer])
+    .rpc({ skipPreflight: true });
+
+    const [vestingSchedule] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vesting_schedule"), payer.toBuffer(), VESTING_SCHEDULE_ID.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    await program.methods
+    .releaseVested()
+    .accounts({
+      payer: payer,
+      beneficiary: payer,
+      vestingSchedule: vestingSchedule,
+      tokenMint: TOKEN_MINT,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+    })
+    .signers([signer])
+    .rpc({ skipPreflight: true });
+
+    await program.methods
+    .revokeVestingSchedule()
+    .accounts({
+      payer: payer,
+      vestingSchedule: vestingSchedule,
+      tokenMint: TOKEN_MINT,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+    })
+    .signers([signer])
+    .rpc({ skipPreflight: true });
   })
 
   it("Start Lotteries", async () => {
@@ -175,7 +464,8 @@ describe("- Draco Protocol Set Up", () => {
       new anchor.BN(1654586517),
       new anchor.BN(1654686517),
       new anchor.BN(1000000),
-      new anchor.BN(50)
+      new anchor.BN(50),
+      lotteryConfig()
     )
     .accounts({
       payer: payer,
@@ -194,7 +484,8 @@ describe("- Draco Protocol Set Up", () => {
       new anchor.BN(Math.floor(Date.now() / 1000)),
       new anchor.BN(Math.floor(Date.now() / 1000) + 1*60),
       new anchor.BN(1000000),
-      new anchor.BN(50)
+      new anchor.BN(50),
+      lotteryConfig()
     )
     .accounts({
       payer: payer,
@@ -213,7 +504,8 @@ describe("- Draco Protocol Set Up", () => {
       new anchor.BN(1755792272),
       new anchor.BN(1756796272),
       new anchor.BN(10000),
-      new anchor.BN(500)
+      new anchor.BN(500),
+      lotteryConfig()
     )
     .accounts({
       payer: payer,
@@ -233,7 +525,10 @@ describe("- Draco Protocol Set Up", () => {
       VALID_AMOUNT_PAY,
     )
     .accounts({
-      payer: payer,
+      feePayer: payer,
+      user: payer,
+      ticketMint: null,
+      ticketHolderAccount: null,
       tokenMint: TOKEN_MINT,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
     })
@@ -254,7 +549,10 @@ describe("- Draco Protocol Set Up", () => {
       VALID_AMOUNT_PAY,
     )
     .accounts({
-      payer: payer,
+      feePayer: payer,
+      user: payer,
+      ticketMint: null,
+      ticketHolderAccount: null,
       tokenMint: TOKEN_MINT,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
     })
@@ -275,7 +573,10 @@ describe("- Draco Protocol Set Up", () => {
       WRONG_AMOUNT_PAY,
     )
     .accounts({
-      payer: payer,
+      feePayer: payer,
+      user: payer,
+      ticketMint: null,
+      ticketHolderAccount: null,
       tokenMint: TOKEN_MINT,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
     })
@@ -295,7 +596,10 @@ describe("- Draco Protocol Set Up", () => {
       VALID_AMOUNT_PAY,
     )
     .accounts({
-      payer: payer,
+      feePayer: payer,
+      user: payer,
+      ticketMint: null,
+      ticketHolderAccount: null,
       tokenMint: TOKEN_MINT,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
     })
@@ -303,6 +607,86 @@ describe("- Draco Protocol Set Up", () => {
     .rpc({ skipPreflight: true });
   })
 
+  let payoutOwner: Keypair;
+
+  it("Buy PAY Lottery Tickets to pay out", async () => {
+    payoutOwner = await fundedWallet(200);
+    for (const combination of [PAYOUT_COMBINATION, SELF_PAYOUT_COMBINATION]) {
+      await program.methods
+        .buyLotteryTicket(
+          PAY_LOTTERY_ID,
+          combination,
+          VALID_AMOUNT_PAY,
+        )
+        .accounts({
+          feePayer: payer,
+          user: payoutOwner.publicKey,
+          ticketMint: null,
+          ticketHolderAccount: null,
+          tokenMint: TOKEN_MINT,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers([signer, payoutOwner])
+        .rpc({ skipPreflight: true });
+    }
+  })
+
+  let batchClaimer: Keypair;
+
+  it("Buy PAY Lottery Tickets to claim in a batch", async () => {
+    batchClaimer = await fundedWallet(100);
+    // The first ticket's rent is paid by its owner, the second one's by a relayer
+    for (const [combination, feePayer] of [[BATCH_COMBINATIONS[0], batchClaimer], [BATCH_COMBINATIONS[1], signer]] as const) {
+      await program.methods
+        .buyLotteryTicket(
+          PAY_LOTTERY_ID,
+          combination,
+          VALID_AMOUNT_PAY,
+        )
+        .accounts({
+          feePayer: feePayer.publicKey,
+          user: batchClaimer.publicKey,
+          ticketMint: null,
+          ticketHolderAccount: null,
+          tokenMint: TOKEN_MINT,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers(feePayer === signer ? [signer, batchClaimer] : [batchClaimer])
+        .rpc({ skipPreflight: true });
+    }
+  })
+
+  let syndicateMember: Keypair;
+
+  it("Pool two members into a PAY Lottery Syndicate", async () => {
+    await program.methods
+      .createSyndicate(DRAWN_SYNDICATE_ID, PAY_LOTTERY_ID)
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    syndicateMember = await fundedWallet(50);
+    await contributeToSyndicate(DRAWN_SYNDICATE_ID, signer, 100);
+    await contributeToSyndicate(DRAWN_SYNDICATE_ID, syndicateMember, 50);
+
+    await program.methods
+      .buySyndicateTicket(DRAWN_SYNDICATE_ID, SYNDICATE_COMBINATION, VALID_AMOUNT_PAY)
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    // More tickets can still be bought, shares aren't final
+    await expectError(withdrawSyndicateShare(DRAWN_SYNDICATE_ID, signer), "SyndicateWithdrawalsNotOpen");
+  })
+
   it("Buy Lottery Ticket LOCK Invalid Amount", async () => {
     try {
     const _ = await program.methods
@@ -312,7 +696,10 @@ describe("- Draco Protocol Set Up", () => {
       WRONG_AMOUNT_LOCK,
     )
     .accounts({
-      payer: payer,
+      feePayer: payer,
+      user: payer,
+      ticketMint: null,
+      ticketHolderAccount: null,
       tokenMint: TOKEN_MINT,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
     })
@@ -332,7 +719,10 @@ describe("- Draco Protocol Set Up", () => {
       VALID_AMOUNT_LOCK,
     )
     .accounts({
-      payer: payer,
+      feePayer: payer,
+      user: payer,
+      ticketMint: null,
+      ticketHolderAccount: null,
       tokenMint: TOKEN_MINT,
       tokenProgram: TOKEN_2022_PROGRAM_ID,
     })
@@ -346,6 +736,22 @@ describe("- Draco Protocol Set Up", () => {
 
     await new Promise(resolve => setTimeout(resolve, 1.2*60*1000));
 
+    await program.methods
+      .closeLotterySales(PAY_LOTTERY_ID)
+      .accounts({
+        payer: payer,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .finalizeTicketSet(PAY_LOTTERY_ID)
+      .accounts({
+        payer: payer,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
     const queue = new anchor.web3.PublicKey("A43DyUGA7s8eXPxqEjJY6EBu1KKbNgfxF8h17VAHn13w");
     let queueAccount: sb.Queue;
     try {
@@ -459,19 +865,232 @@ describe("- Draco Protocol Set Up", () => {
     );
   })
 
+  it("Pay out tickets without a bounty during the grace period or for their owner", async () => {
+    const payoutTicket = (combination: string, cranker: Keypair) => program.methods
+      .payoutTicket(PAY_LOTTERY_ID, combination)
+      .accountsPartial({
+        payer: cranker.publicKey,
+        owner: payoutOwner.publicKey,
+        ticket: ticketPDA(PAY_LOTTERY_ID, payoutOwner.publicKey, combination),
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([cranker])
+      .rpc({ skipPreflight: true });
+
+    for (const [combination, cranker] of [[PAYOUT_COMBINATION, signer], [SELF_PAYOUT_COMBINATION, payoutOwner]] as const) {
+      const ticket = ticketPDA(PAY_LOTTERY_ID, payoutOwner.publicKey, combination);
+      const { prize } = await program.methods
+        .previewPrize(PAY_LOTTERY_ID, combination)
+        .accounts({
+          ticket: ticket,
+          tokenMint: TOKEN_MINT,
+        })
+        .view();
+      const ownerBefore = await tokenBalance(payoutOwner.publicKey);
+      const crankerBefore = await tokenBalance(cranker.publicKey);
+
+      await payoutTicket(combination, cranker);
+
+      assert.isTrue((await program.account.ticket.fetch(ticket)).isClaimed);
+      // The draw just happened and the owner can't crank for a bounty, so only the prize moves
+      assert.equal(await tokenBalance(payoutOwner.publicKey) - ownerBefore, await scaled(BigInt(prize.toString())));
+      if (cranker === signer) {
+        assert.equal(await tokenBalance(payer), crankerBefore);
+      }
+    }
+
+    await expectError(payoutTicket(PAYOUT_COMBINATION, signer), "TicketAlreadyClaimed");
+  })
+
+  it("Claim a Syndicate prize and withdraw pro-rata shares around it", async () => {
+    const syndicateVault = syndicateVaultPDA(DRAWN_SYNDICATE_ID);
+    const ticket = ticketPDA(PAY_LOTTERY_ID, syndicatePDA(DRAWN_SYNDICATE_ID), SYNDICATE_COMBINATION);
+    const { prize } = await program.methods
+      .previewPrize(PAY_LOTTERY_ID, SYNDICATE_COMBINATION)
+      .accounts({
+        ticket: ticket,
+        tokenMint: TOKEN_MINT,
+      })
+      .view();
+    const managerBefore = await tokenBalance(payer);
+    const memberBefore = await tokenBalance(syndicateMember.publicKey);
+
+    // Before the prize only the 100 DRACO left after the ticket is shared
+    await withdrawSyndicateShare(DRAWN_SYNDICATE_ID, signer);
+    const earlyShare = await scaled(BigInt(100)) * BigInt(100) / BigInt(150);
+    assert.equal(await tokenBalance(payer) - managerBefore, earlyShare);
+
+    const vaultBeforeClaim = await vaultBalance(syndicateVault);
+    await program.methods
+      .claimSyndicatePrize(DRAWN_SYNDICATE_ID, SYNDICATE_COMBINATION)
+      .accountsPartial({
+        payer: payer,
+        ticket: ticket,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+    const scaledPrize = await scaled(BigInt(prize.toString()));
+    assert.equal(await vaultBalance(syndicateVault) - vaultBeforeClaim, scaledPrize);
+    assert.isTrue((await program.account.ticket.fetch(ticket)).isClaimed);
+
+    // Shares are taken of everything the vault ever held, whenever a member withdraws
+    const everHeld = await scaled(BigInt(100)) + scaledPrize;
+    await withdrawSyndicateShare(DRAWN_SYNDICATE_ID, syndicateMember);
+    await withdrawSyndicateShare(DRAWN_SYNDICATE_ID, signer);
+    assert.equal(await tokenBalance(payer) - managerBefore, everHeld * BigInt(100) / BigInt(150));
+    assert.equal(await tokenBalance(syndicateMember.publicKey) - memberBefore, everHeld * BigInt(50) / BigInt(150));
+    await expectError(withdrawSyndicateShare(DRAWN_SYNDICATE_ID, syndicateMember), "NothingToWithdraw");
+  })
+
+  const payLotteryVault = PublicKey.findProgramAddressSync(
+    [Buffer.from("lottery_token_account"), PAY_LOTTERY_ID.toArrayLike(Buffer, "le", 8)],
+    program.programId
+  )[0];
+  let prizeBreakdown: { prize: anchor.BN, sourceVault: PublicKey };
+
+  it("Preview Lottery Prize for finished lottery", async () => {
+    prizeBreakdown = await program.methods
+      .previewPrize(
+        PAY_LOTTERY_ID,
+        VALID_COMBINATION,
+      )
+      .accounts({
+        ticket: ticketPDA(PAY_LOTTERY_ID, payer, VALID_COMBINATION),
+        tokenMint: TOKEN_MINT,
+      })
+      .view();
+
+    // The lottery vault holds the ticket sales and the initial prize pool, it covers the prize
+    assert.isTrue(prizeBreakdown.sourceVault.equals(payLotteryVault));
+    assert.isFalse((await program.account.ticket.fetch(ticketPDA(PAY_LOTTERY_ID, payer, VALID_COMBINATION))).isClaimed);
+  })
+
   it("Claim Lottery Prize for finished lottery", async () => {
+    const balanceBefore = await tokenBalance(payer);
+    const vaultBefore = await vaultBalance(payLotteryVault);
+
     const txSig = await program.methods
       .claimLotteryPrizeForCombination(
         PAY_LOTTERY_ID,
         VALID_COMBINATION,
       )
       .accounts({
-        payer: payer,
+        feePayer: payer,
+        user: payer,
+        ticket: ticketPDA(PAY_LOTTERY_ID, payer, VALID_COMBINATION),
+        ticketMint: null,
+        ticketHolderAccount: null,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    // The claim pays exactly the previewed prize, out of the previewed vault
+    const scaledPrize = await scaled(BigInt(prizeBreakdown.prize.toString()));
+    assert.equal(await tokenBalance(payer) - balanceBefore, scaledPrize);
+    assert.equal(vaultBefore - await vaultBalance(payLotteryVault), scaledPrize);
+  })
+
+  it("Claim Lottery Prizes for already claimed tickets", async () => {
+    await expectError(
+      program.methods
+        .claimLotteryPrizes(
+          PAY_LOTTERY_ID,
+          false
+        )
+        .accounts({
+          payer: payer,
+          tokenMint: TOKEN_MINT,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .remainingAccounts([
+          { pubkey: ticketPDA(PAY_LOTTERY_ID, payer, VALID_COMBINATION), isWritable: true, isSigner: false },
+        ])
+        .signers([signer])
+        .rpc({ skipPreflight: true }),
+      "TicketAlreadyClaimed"
+    );
+  })
+
+  it("Claim Lottery Prizes in a batch and close the tickets whose rent the payer paid", async () => {
+    const tickets = BATCH_COMBINATIONS.map(combination => ticketPDA(PAY_LOTTERY_ID, batchClaimer.publicKey, combination));
+    let totalPrize = BigInt(0);
+    for (const [index, combination] of BATCH_COMBINATIONS.entries()) {
+      const { prize } = await program.methods
+        .previewPrize(PAY_LOTTERY_ID, combination)
+        .accounts({
+          ticket: tickets[index],
+          tokenMint: TOKEN_MINT,
+        })
+        .view();
+      totalPrize += BigInt(prize.toString());
+    }
+
+    const balanceBefore = await tokenBalance(batchClaimer.publicKey);
+    await program.methods
+      .claimLotteryPrizes(
+        PAY_LOTTERY_ID,
+        true
+      )
+      .accounts({
+        payer: batchClaimer.publicKey,
         tokenMint: TOKEN_MINT,
         tokenProgram: TOKEN_2022_PROGRAM_ID,
       })
+      .remainingAccounts(tickets.map(ticket => ({ pubkey: ticket, isWritable: true, isSigner: false })))
+      .signers([batchClaimer])
+      .rpc({ skipPreflight: true });
+
+    // Both prizes arrive in a single transfer
+    assert.equal(await tokenBalance(batchClaimer.publicKey) - balanceBefore, await scaled(totalPrize));
+
+    // The relayer paid the second ticket's rent, it stays open for close_lottery_ticket
+    assert.isNull(await connection.getAccountInfo(tickets[0]));
+    const relayedTicket = await program.account.ticket.fetch(tickets[1]);
+    assert.isTrue(relayedTicket.isClaimed);
+    assert.isTrue(relayedTicket.rentPayer.equals(payer));
+  })
+
+  const expireUnclaimedPrizes = (lotteryId: anchor.BN) => program.methods
+    .expireUnclaimedPrizes(
+      lotteryId
+    )
+    .accounts({
+      payer: payer,
+    })
+    .signers([signer])
+    .rpc({ skipPreflight: true });
+
+  it("Expire unclaimed prizes before the claim deadline", async () => {
+    await expectError(expireUnclaimedPrizes(PAY_LOTTERY_ID), "ClaimWindowNotExpired");
+  })
+
+  it("Extend the Lottery claim deadline", async () => {
+    const [payLotteryState] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lottery_state"), PAY_LOTTERY_ID.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const extendClaimDeadline = (claimDeadline: anchor.BN) => program.methods
+      .extendLotteryClaimDeadline(PAY_LOTTERY_ID, claimDeadline)
+      .accounts({
+        payer: payer,
+      })
       .signers([signer])
       .rpc({ skipPreflight: true });
+
+    // The deadline can only move forward
+    const { claimDeadline } = await program.account.lotteryState.fetch(payLotteryState);
+    await expectError(extendClaimDeadline(claimDeadline), "InvalidClaimDeadline");
+
+    await extendClaimDeadline(claimDeadline.addn(24*60*60));
+    const lotteryState = await program.account.lotteryState.fetch(payLotteryState);
+    assert.isTrue(lotteryState.claimDeadline.eq(claimDeadline.addn(24*60*60)));
+    assert.isNull(lotteryState.expiredUnclaimedAmount);
+    await expectError(expireUnclaimedPrizes(PAY_LOTTERY_ID), "ClaimWindowNotExpired");
   })
 
   it("Close Lottery not old enough", async () => {
@@ -505,4 +1124,1054 @@ describe("- Draco Protocol Set Up", () => {
       .signers([signer])
       .rpc({ skipPreflight: true });
   })
-});
\ No newline at end of file
+
+  it("Cancel Lottery and refund its tickets", async () => {
+    await program.methods
+      .startLottery(
+        CANCELLED_LOTTERY_ID,
+        "Test Lottery CANCEL",
+        "Test Lottery Description CANCEL",
+        0,
+        new anchor.BN(Math.floor(Date.now() / 1000) - 60),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 60*60),
+        new anchor.BN(1000000),
+        new anchor.BN(50),
+        lotteryConfig({
+          maxParticipants: new anchor.BN(100),
+          maxTicketsPerWallet: new anchor.BN(1),
+        })
+      )
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .buyLotteryTicket(
+        CANCELLED_LOTTERY_ID,
+        VALID_COMBINATION,
+        VALID_AMOUNT_PAY,
+      )
+      .accounts({
+        feePayer: payer,
+        user: payer,
+        ticketMint: null,
+        ticketHolderAccount: null,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .cancelLottery(CANCELLED_LOTTERY_ID)
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .refundTicket(
+        CANCELLED_LOTTERY_ID,
+        VALID_COMBINATION,
+      )
+      .accounts({
+        payer: payer,
+        participant: payer,
+        rentPayer: payer,
+        participantRentPayer: payer,
+        recipient: payer,
+        ticket: ticketPDA(CANCELLED_LOTTERY_ID, payer, VALID_COMBINATION),
+        ticketMint: null,
+        ticketHolderAccount: null,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    // The refunded ticket was the only one of the wallet, so its participant is gone
+    assert.isNull(await connection.getAccountInfo(participantPDA(CANCELLED_LOTTERY_ID, payer)));
+  })
+
+  it("Update scheduled Lottery and top up its prize pool", async () => {
+    await program.methods
+      .startLottery(
+        SCHEDULED_LOTTERY_ID,
+        "Test Lottery SCHEDULED",
+        "Test Lottery Description SCHEDULED",
+        0,
+        new anchor.BN(Math.floor(Date.now() / 1000) + 24*60*60),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 48*60*60),
+        new anchor.BN(1000000),
+        new anchor.BN(50),
+        lotteryConfig({
+          salesCloseBuffer: new anchor.BN(60*60),
+        })
+      )
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .updateScheduledLottery(
+        SCHEDULED_LOTTERY_ID,
+        "Test Lottery UPDATED",
+        null,
+        null,
+        new anchor.BN(Math.floor(Date.now() / 1000) + 72*60*60),
+        new anchor.BN(100)
+      )
+      .accounts({
+        payer: payer,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .topUpLotteryPrizePool(
+        SCHEDULED_LOTTERY_ID,
+        new anchor.BN(1000),
+      )
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+  })
+
+  const scheduledLotteryState = PublicKey.findProgramAddressSync(
+    [Buffer.from("lottery_state"), SCHEDULED_LOTTERY_ID.toArrayLike(Buffer, "le", 8)],
+    program.programId
+  )[0];
+  const scheduledLotteryVault = PublicKey.findProgramAddressSync(
+    [Buffer.from("lottery_token_account"), SCHEDULED_LOTTERY_ID.toArrayLike(Buffer, "le", 8)],
+    program.programId
+  )[0];
+  const sponsorshipPDA = (lotteryId: anchor.BN, sponsor: PublicKey) => PublicKey.findProgramAddressSync(
+    [Buffer.from("sponsorship"), lotteryId.toArrayLike(Buffer, "le", 8), sponsor.toBuffer()],
+    program.programId
+  )[0];
+
+  let sponsor: Keypair;
+
+  it("Sponsor Lottery", async () => {
+    const sponsoredBefore = (await program.account.lotteryState.fetch(scheduledLotteryState)).sponsoredPrizePool;
+    const vaultBefore = await vaultBalance(scheduledLotteryVault);
+
+    await program.methods
+      .sponsorLottery(
+        SCHEDULED_LOTTERY_ID,
+        new anchor.BN(500),
+      )
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    sponsor = await fundedWallet(100);
+    await program.methods
+      .sponsorLottery(
+        SCHEDULED_LOTTERY_ID,
+        new anchor.BN(100),
+      )
+      .accounts({
+        payer: sponsor.publicKey,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([sponsor])
+      .rpc({ skipPreflight: true });
+
+    const lotteryState = await program.account.lotteryState.fetch(scheduledLotteryState);
+    assert.equal(lotteryState.sponsoredPrizePool.sub(sponsoredBefore).toNumber(), 600);
+    assert.equal(await vaultBalance(scheduledLotteryVault) - vaultBefore, await scaled(BigInt(600)));
+    const sponsorship = await program.account.sponsorship.fetch(sponsorshipPDA(SCHEDULED_LOTTERY_ID, payer));
+    assert.equal(sponsorship.amount.toNumber(), 500);
+  })
+
+  it("Refund Lottery sponsorships once the Lottery is cancelled", async () => {
+    const refundSponsorship = (sponsorKey: PublicKey) => program.methods
+      .refundLotterySponsorship(SCHEDULED_LOTTERY_ID)
+      .accountsPartial({
+        payer: payer,
+        sponsor: sponsorKey,
+        sponsorship: sponsorshipPDA(SCHEDULED_LOTTERY_ID, sponsorKey),
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await expectError(refundSponsorship(payer), "LotteryNotCancelled");
+
+    await program.methods
+      .cancelLottery(SCHEDULED_LOTTERY_ID)
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    // The first refund moves the lottery from Cancelled to Refunding, the next ones stay there
+    for (const [sponsorKey, amount] of [[payer, 500], [sponsor.publicKey, 100]] as const) {
+      const { sponsoredPrizePool } = await program.account.lotteryState.fetch(scheduledLotteryState);
+      const balanceBefore = await tokenBalance(sponsorKey);
+
+      await refundSponsorship(sponsorKey);
+
+      const lotteryState = await program.account.lotteryState.fetch(scheduledLotteryState);
+      assert.property(lotteryState.status, "refunding");
+      assert.equal(sponsoredPrizePool.sub(lotteryState.sponsoredPrizePool).toNumber(), amount);
+      assert.equal(await tokenBalance(sponsorKey) - balanceBefore, await scaled(BigInt(amount)));
+      assert.isNull(await connection.getAccountInfo(sponsorshipPDA(SCHEDULED_LOTTERY_ID, sponsorKey)));
+    }
+  })
+
+  it("Close claimed Lottery Ticket", async () => {
+    const txSig = await program.methods
+      .closeLotteryTicket(
+        PAY_LOTTERY_ID,
+        VALID_COMBINATION,
+      )
+      .accounts({
+        payer: payer,
+        rentPayer: payer,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+  })
+
+  it("Close cancelled Lottery accounts", async () => {
+    const txSig = await program.methods
+      .closeLotteryAccounts(
+        CANCELLED_LOTTERY_ID
+      )
+      .accounts({
+        payer: payer,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+  })
+
+  it("Buy Lottery Ticket NFT", async () => {
+    await program.methods
+      .startLottery(
+        NFT_LOTTERY_ID,
+        "Test Lottery NFT",
+        "Test Lottery Description NFT",
+        0,
+        new anchor.BN(Math.floor(Date.now() / 1000) - 60),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 60*60),
+        new anchor.BN(1000000),
+        new anchor.BN(50),
+        lotteryConfig({
+          ticketNftMode: true,
+        })
+      )
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .buyLotteryTicket(
+        NFT_LOTTERY_ID,
+        VALID_COMBINATION,
+        VALID_AMOUNT_PAY,
+      )
+      .accounts({
+        feePayer: payer,
+        user: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+  })
+
+  it("List, delist and buy a listed Lottery Ticket", async () => {
+    await program.methods
+      .startLottery(
+        MARKETPLACE_LOTTERY_ID,
+        "Test Lottery MARKET",
+        "Test Lottery Description MARKET",
+        0,
+        new anchor.BN(Math.floor(Date.now() / 1000) - 60),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 60*60),
+        new anchor.BN(1000000),
+        new anchor.BN(50),
+        lotteryConfig()
+      )
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .buyLotteryTicket(
+        MARKETPLACE_LOTTERY_ID,
+        VALID_COMBINATION,
+        VALID_AMOUNT_PAY,
+      )
+      .accounts({
+        feePayer: payer,
+        user: payer,
+        ticketMint: null,
+        ticketHolderAccount: null,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    const ticket = ticketPDA(MARKETPLACE_LOTTERY_ID, payer, VALID_COMBINATION);
+    const [ticketListing] = PublicKey.findProgramAddressSync(
+      [Buffer.from("ticket_listing"), ticket.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .listTicket(MARKETPLACE_LOTTERY_ID, VALID_COMBINATION, new anchor.BN(100))
+      .accounts({
+        payer: payer,
+        ticket: ticket,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .delistTicket()
+      .accounts({
+        payer: payer,
+        seller: payer,
+        ticket: ticket,
+        ticketListing: ticketListing,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .listTicket(MARKETPLACE_LOTTERY_ID, VALID_COMBINATION, new anchor.BN(100))
+      .accounts({
+        payer: payer,
+        ticket: ticket,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    // Listed tickets are escrowed, the seller can't top them up
+    assert.isTrue((await program.account.ticket.fetch(ticket)).participant.equals(ticketListing));
+
+    // The seller can't buy its own listing, the buyer needs SOL and DRACO of its own
+    const buyer = Keypair.generate();
+    await connection.confirmTransaction(
+      await connection.requestAirdrop(buyer.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+    );
+    const mint = await getMint(connection, TOKEN_MINT, undefined, TOKEN_2022_PROGRAM_ID);
+    const buyerTokenAccount = await getOrCreateAssociatedTokenAccount(
+      connection, signer, TOKEN_MINT, buyer.publicKey, false, undefined, undefined, TOKEN_2022_PROGRAM_ID
+    );
+    await transferChecked(
+      connection,
+      signer,
+      getAssociatedTokenAddressSync(TOKEN_MINT, payer, false, TOKEN_2022_PROGRAM_ID),
+      TOKEN_MINT,
+      buyerTokenAccount.address,
+      signer,
+      BigInt(100) * BigInt(10) ** BigInt(mint.decimals),
+      mint.decimals,
+      [],
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    const [marketplaceLotteryState] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lottery_state"), MARKETPLACE_LOTTERY_ID.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const entriesBefore = (await program.account.lotteryState.fetch(marketplaceLotteryState)).ticketSetEntries;
+
+    await program.methods
+      .buyListedTicket(MARKETPLACE_LOTTERY_ID, VALID_COMBINATION)
+      .accounts({
+        payer: buyer.publicKey,
+        seller: payer,
+        ticketRentPayer: payer,
+        ticket: ticket,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([buyer])
+      .rpc({ skipPreflight: true });
+
+    // The ticket moved to the buyer's own ticket account along with its participant units
+    assert.isNull(await connection.getAccountInfo(ticket));
+    const buyerTicket = await program.account.ticket.fetch(ticketPDA(MARKETPLACE_LOTTERY_ID, buyer.publicKey, VALID_COMBINATION));
+    assert.isTrue(buyerTicket.participant.equals(buyer.publicKey));
+    assert.isTrue(buyerTicket.rentPayer.equals(buyer.publicKey));
+    // The sale is part of the ticket set committed to before the draw
+    const lotteryStateAfterSale = await program.account.lotteryState.fetch(marketplaceLotteryState);
+    assert.equal(lotteryStateAfterSale.ticketSetEntries.toNumber(), entriesBefore.toNumber() + 1);
+    const buyerParticipant = await program.account.participant.fetch(participantPDA(MARKETPLACE_LOTTERY_ID, buyer.publicKey));
+    assert.equal(buyerParticipant.ticketsCount.toNumber(), 1);
+    const sellerParticipant = await program.account.participant.fetch(participantPDA(MARKETPLACE_LOTTERY_ID, payer));
+    assert.equal(sellerParticipant.ticketsCount.toNumber(), 0);
+
+    // So the seller can buy the same combination again
+    await program.methods
+      .buyLotteryTicket(
+        MARKETPLACE_LOTTERY_ID,
+        VALID_COMBINATION,
+        VALID_AMOUNT_PAY,
+      )
+      .accounts({
+        feePayer: payer,
+        user: payer,
+        ticketMint: null,
+        ticketHolderAccount: null,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+  })
+
+  it("Contribute to a Syndicate and buy a Lottery Ticket with it", async () => {
+    await program.methods
+      .createSyndicate(SYNDICATE_ID, MARKETPLACE_LOTTERY_ID)
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .contributeToSyndicate(SYNDICATE_ID, new anchor.BN(100))
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .buySyndicateTicket(SYNDICATE_ID, "S2S3S4S5", VALID_AMOUNT_PAY)
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+  })
+
+  it("Refund a Syndicate ticket of a cancelled Lottery back to its members", async () => {
+    await program.methods
+      .startLottery(
+        SYNDICATE_LOTTERY_ID,
+        "Test Lottery SYNDICATE",
+        "Test Lottery Description SYNDICATE",
+        0,
+        new anchor.BN(Math.floor(Date.now() / 1000) - 60),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 60*60),
+        new anchor.BN(1000000),
+        new anchor.BN(50),
+        lotteryConfig()
+      )
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await program.methods
+      .createSyndicate(REFUNDED_SYNDICATE_ID, SYNDICATE_LOTTERY_ID)
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    const member = await fundedWallet(40);
+    const managerBefore = await tokenBalance(payer);
+    await contributeToSyndicate(REFUNDED_SYNDICATE_ID, signer, 60);
+    await contributeToSyndicate(REFUNDED_SYNDICATE_ID, member, 40);
+    await program.methods
+      .buySyndicateTicket(REFUNDED_SYNDICATE_ID, SYNDICATE_COMBINATION, VALID_AMOUNT_PAY)
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+    await expectError(withdrawSyndicateShare(REFUNDED_SYNDICATE_ID, member), "SyndicateWithdrawalsNotOpen");
+
+    await program.methods
+      .cancelLottery(SYNDICATE_LOTTERY_ID)
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    const syndicate = syndicatePDA(REFUNDED_SYNDICATE_ID);
+    const ticket = ticketPDA(SYNDICATE_LOTTERY_ID, syndicate, SYNDICATE_COMBINATION);
+    await program.methods
+      .refundSyndicateTicket(REFUNDED_SYNDICATE_ID, SYNDICATE_COMBINATION)
+      .accountsPartial({
+        payer: payer,
+        manager: payer,
+        ticket: ticket,
+        participant: participantPDA(SYNDICATE_LOTTERY_ID, syndicate),
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    // The refund goes back to the pool and the syndicate's ticket and participant are closed
+    assert.equal(await vaultBalance(syndicateVaultPDA(REFUNDED_SYNDICATE_ID)), await scaled(BigInt(100)));
+    assert.isNull(await connection.getAccountInfo(ticket));
+    assert.isNull(await connection.getAccountInfo(participantPDA(SYNDICATE_LOTTERY_ID, syndicate)));
+
+    // With nothing won every member gets exactly its contribution back
+    await withdrawSyndicateShare(REFUNDED_SYNDICATE_ID, member);
+    await withdrawSyndicateShare(REFUNDED_SYNDICATE_ID, signer);
+    assert.equal(await tokenBalance(member.publicKey), await scaled(BigInt(40)));
+    assert.equal(await tokenBalance(payer), managerBefore);
+    await expectError(withdrawSyndicateShare(REFUNDED_SYNDICATE_ID, signer), "NothingToWithdraw");
+  })
+
+  it("Enforce the per wallet ticket and participant caps", async () => {
+    await program.methods
+      .startLottery(
+        CAPPED_LOTTERY_ID,
+        "Test Lottery CAPPED",
+        "Test Lottery Description CAPPED",
+        0,
+        new anchor.BN(Math.floor(Date.now() / 1000) - 60),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 60*60),
+        new anchor.BN(1000000),
+        new anchor.BN(50),
+        lotteryConfig({
+          maxParticipants: new anchor.BN(1),
+          maxTicketsPerWallet: new anchor.BN(2),
+          claimDeadline: new anchor.BN(Math.floor(Date.now() / 1000) + 2*60*60),
+        })
+      )
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    const buyTicket = (user: Keypair, combination: string, amount: anchor.BN) => program.methods
+      .buyLotteryTicket(
+        CAPPED_LOTTERY_ID,
+        combination,
+        amount,
+      )
+      .accounts({
+        feePayer: payer,
+        user: user.publicKey,
+        ticketMint: null,
+        ticketHolderAccount: null,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer, user])
+      .rpc({ skipPreflight: true });
+
+    // Two ticket units of min_tokens_per_participant in a single combination
+    await buyTicket(signer, VALID_COMBINATION, new anchor.BN(100));
+    const participant = await program.account.participant.fetch(participantPDA(CAPPED_LOTTERY_ID, payer));
+    assert.equal(participant.ticketsCount.toNumber(), 2);
+
+    // Topping up or buying another combination both count against the cap
+    await expectError(buyTicket(signer, VALID_COMBINATION, VALID_AMOUNT_PAY), "MaxTicketsPerWalletReached");
+    await expectError(buyTicket(signer, "S2S3S4S5", VALID_AMOUNT_PAY), "MaxTicketsPerWalletReached");
+
+    await expectError(buyTicket(Keypair.generate(), VALID_COMBINATION, VALID_AMOUNT_PAY), "MaxParticipantsReached");
+    const lotteryState = await program.account.lotteryState.fetch(
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("lottery_state"), CAPPED_LOTTERY_ID.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0]
+    );
+    assert.equal(lotteryState.participantsCount.toNumber(), 1);
+  })
+
+  it("Claim a Merkle airdrop once per leaf", async () => {
+    const mint = await getMint(connection, TOKEN_MINT, undefined, TOKEN_2022_PROGRAM_ID);
+    const other = Keypair.generate();
+    const leaves = [merkleLeaf(0, payer, 100), merkleLeaf(1, other.publicKey, 200)];
+    await createAirdrop(MERKLE_AIRDROP_ID, 300, {
+      amountPerClaim: new anchor.BN(0),
+      mode: { merkle: { merkleRoot: Array.from(merkleNode(leaves[0], leaves[1])) } },
+      vesting: null,
+      eligibility: null,
+    });
+
+    // A forged amount or the leaf of another wallet doesn't verify
+    const proof = [Array.from(leaves[1])];
+    await expectError(
+      claimAirdrop(MERKLE_AIRDROP_ID, signer, { index: new anchor.BN(0), amount: new anchor.BN(300), proof }, null)
+        .rpc({ skipPreflight: true }),
+      "InvalidMerkleProof"
+    );
+    await expectError(
+      claimAirdrop(MERKLE_AIRDROP_ID, signer, { index: new anchor.BN(1), amount: new anchor.BN(200), proof: [Array.from(leaves[0])] }, null)
+        .rpc({ skipPreflight: true }),
+      "InvalidMerkleProof"
+    );
+
+    const balanceBefore = await tokenBalance(payer);
+    await claimAirdrop(MERKLE_AIRDROP_ID, signer, { index: new anchor.BN(0), amount: new anchor.BN(100), proof }, null)
+      .rpc({ skipPreflight: true });
+    assert.equal(await tokenBalance(payer) - balanceBefore, BigInt(100) * BigInt(10) ** BigInt(mint.decimals));
+
+    // The leaf is marked in the bitmap, claiming it again fails
+    await expectError(
+      claimAirdrop(MERKLE_AIRDROP_ID, signer, { index: new anchor.BN(0), amount: new anchor.BN(100), proof }, null)
+        .rpc({ skipPreflight: true }),
+      "AirdropAlreadyClaimed"
+    );
+    const airdrop = await program.account.airdrop.fetch(airdropPDA("airdrop", MERKLE_AIRDROP_ID));
+    assert.equal(airdrop.supplied.toNumber(), 100);
+  })
+
+  it("Claim a Signed airdrop with an Ed25519 signature", async () => {
+    const mint = await getMint(connection, TOKEN_MINT, undefined, TOKEN_2022_PROGRAM_ID);
+    const airdropSigner = Keypair.generate();
+    await createAirdrop(SIGNED_AIRDROP_ID, 300, {
+      amountPerClaim: new anchor.BN(0),
+      mode: { signed: { signer: airdropSigner.publicKey } },
+      vesting: null,
+      eligibility: null,
+    });
+
+    const amount = new anchor.BN(100);
+    const expiry = new anchor.BN(Math.floor(Date.now() / 1000) + 60*60);
+    const signedClaim = { amount, expiry };
+    const signature = claimSignature(airdropSigner, SIGNED_AIRDROP_ID, payer, amount, expiry);
+
+    // The signature has to be in the instruction right before the claim
+    await expectError(
+      claimAirdrop(SIGNED_AIRDROP_ID, signer, null, signedClaim).rpc({ skipPreflight: true }),
+      "InvalidClaimSignature"
+    );
+    await expectError(
+      claimAirdrop(SIGNED_AIRDROP_ID, signer, null, signedClaim)
+        .postInstructions([signature])
+        .rpc({ skipPreflight: true }),
+      "InvalidClaimSignature"
+    );
+
+    // Signed by another key or for another amount
+    await expectError(
+      claimAirdrop(SIGNED_AIRDROP_ID, signer, null, signedClaim)
+        .preInstructions([claimSignature(Keypair.generate(), SIGNED_AIRDROP_ID, payer, amount, expiry)])
+        .rpc({ skipPreflight: true }),
+      "InvalidClaimSignature"
+    );
+    await expectError(
+      claimAirdrop(SIGNED_AIRDROP_ID, signer, null, { amount: new anchor.BN(200), expiry })
+        .preInstructions([signature])
+        .rpc({ skipPreflight: true }),
+      "InvalidClaimSignature"
+    );
+
+    const expired = new anchor.BN(Math.floor(Date.now() / 1000) - 60*60);
+    await expectError(
+      claimAirdrop(SIGNED_AIRDROP_ID, signer, null, { amount, expiry: expired })
+        .preInstructions([claimSignature(airdropSigner, SIGNED_AIRDROP_ID, payer, amount, expired)])
+        .rpc({ skipPreflight: true }),
+      "ClaimSignatureExpired"
+    );
+
+    const balanceBefore = await tokenBalance(payer);
+    await claimAirdrop(SIGNED_AIRDROP_ID, signer, null, signedClaim)
+      .preInstructions([signature])
+      .rpc({ skipPreflight: true });
+    assert.equal(await tokenBalance(payer) - balanceBefore, BigInt(100) * BigInt(10) ** BigInt(mint.decimals));
+    const airdropClaimed = await program.account.airdropClaimed.fetch(
+      airdropPDA("airdrop_claimed", SIGNED_AIRDROP_ID, payer.toBuffer())
+    );
+    assert.isTrue(airdropClaimed.rentPayer.equals(payer));
+  })
+
+  it("Scale airdrop claims by the holder balance tier", async () => {
+    const mint = await getMint(connection, TOKEN_MINT, undefined, TOKEN_2022_PROGRAM_ID);
+    const draco = (amount: number) => new anchor.BN(amount).mul(new anchor.BN(10).pow(new anchor.BN(mint.decimals)));
+    await createAirdrop(TIERED_AIRDROP_ID, 1000, {
+      amountPerClaim: new anchor.BN(100),
+      mode: { open: {} },
+      vesting: null,
+      eligibility: {
+        requiredMint: TOKEN_MINT,
+        minBalance: draco(5),
+        registrationDeadlineSlot: null,
+        tiers: [
+          { minBalance: draco(5), amountBps: new anchor.BN(15_000) },
+          { minBalance: draco(10), amountBps: new anchor.BN(20_000) },
+          { minBalance: draco(20), amountBps: new anchor.BN(30_000) },
+        ],
+      },
+    });
+
+    const holder = async (balance: number) => {
+      const wallet = Keypair.generate();
+      const tokenAccount = await getOrCreateAssociatedTokenAccount(
+        connection, signer, TOKEN_MINT, wallet.publicKey, false, undefined, undefined, TOKEN_2022_PROGRAM_ID
+      );
+      await transferChecked(
+        connection,
+        signer,
+        getAssociatedTokenAddressSync(TOKEN_MINT, payer, false, TOKEN_2022_PROGRAM_ID),
+        TOKEN_MINT,
+        tokenAccount.address,
+        signer,
+        BigInt(draco(balance).toString()),
+        mint.decimals,
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      return { wallet, tokenAccount: tokenAccount.address };
+    };
+
+    const smallHolder = await holder(1);
+    await expectError(
+      claimAirdrop(TIERED_AIRDROP_ID, smallHolder.wallet, null, null, smallHolder.tokenAccount).rpc({ skipPreflight: true }),
+      "NotEligibleForAirdrop"
+    );
+
+    // 10 DRACO reaches the second tier, which doubles the claim
+    const tieredHolder = await holder(10);
+    await claimAirdrop(TIERED_AIRDROP_ID, tieredHolder.wallet, null, null, tieredHolder.tokenAccount)
+      .rpc({ skipPreflight: true });
+    assert.equal(
+      (await tokenBalance(tieredHolder.wallet.publicKey)).toString(),
+      draco(10 + 200).toString()
+    );
+  })
+
+  it("Claim an airdrop into a Lottery Ticket", async () => {
+    const mint = await getMint(connection, TOKEN_MINT, undefined, TOKEN_2022_PROGRAM_ID);
+    await program.methods
+      .startLottery(
+        AIRDROP_LOTTERY_ID,
+        "Test Lottery AIRDROP",
+        "Test Lottery Description AIRDROP",
+        0,
+        new anchor.BN(Math.floor(Date.now() / 1000) - 60),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 60*60),
+        new anchor.BN(1000000),
+        new anchor.BN(50),
+        lotteryConfig()
+      )
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+
+    await createAirdrop(TICKET_AIRDROP_ID, 1000, {
+      amountPerClaim: VALID_AMOUNT_PAY,
+      mode: { open: {} },
+      vesting: null,
+      eligibility: null,
+    });
+
+    const [lotteryTokenAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lottery_token_account"), AIRDROP_LOTTERY_ID.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const vaultBalanceBefore = BigInt((await connection.getTokenAccountBalance(lotteryTokenAccount)).value.amount);
+
+    // The claimer has no DRACO account, the claim goes straight into the lottery vault
+    const user = Keypair.generate();
+    await program.methods
+      .claimAirdropIntoTicket(TICKET_AIRDROP_ID, null, null, AIRDROP_LOTTERY_ID, VALID_COMBINATION)
+      .accountsPartial({
+        claimAirdrop: {
+          feePayer: payer,
+          user: user.publicKey,
+          userTokenAccount: null,
+          airdropTokenAccount: airdropPDA("airdrop_token_account", TICKET_AIRDROP_ID),
+          airdrop: airdropPDA("airdrop", TICKET_AIRDROP_ID),
+          airdropClaimed: airdropPDA("airdrop_claimed", TICKET_AIRDROP_ID, user.publicKey.toBuffer()),
+          airdropClaimBitmap: null,
+          instructionsSysvar: null,
+          airdropVestingPosition: null,
+          eligibilityTokenAccount: null,
+          airdropRegistration: null,
+          tokenMint: TOKEN_MINT,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        },
+        lotteryTokenAccount: lotteryTokenAccount,
+        ticket: ticketPDA(AIRDROP_LOTTERY_ID, user.publicKey, VALID_COMBINATION),
+        participant: participantPDA(AIRDROP_LOTTERY_ID, user.publicKey),
+      })
+      .signers([signer, user])
+      .rpc({ skipPreflight: true });
+
+    const ticket = await program.account.ticket.fetch(ticketPDA(AIRDROP_LOTTERY_ID, user.publicKey, VALID_COMBINATION));
+    assert.equal(ticket.amount.toNumber(), VALID_AMOUNT_PAY.toNumber());
+    assert.isTrue(ticket.participant.equals(user.publicKey));
+    assert.isTrue(ticket.rentPayer.equals(payer));
+    assert.isNull(await connection.getAccountInfo(
+      getAssociatedTokenAddressSync(TOKEN_MINT, user.publicKey, false, TOKEN_2022_PROGRAM_ID)
+    ));
+    const vaultBalanceAfter = BigInt((await connection.getTokenAccountBalance(lotteryTokenAccount)).value.amount);
+    assert.equal(
+      vaultBalanceAfter - vaultBalanceBefore,
+      BigInt(VALID_AMOUNT_PAY.toNumber()) * BigInt(10) ** BigInt(mint.decimals)
+    );
+  })
+
+  it("Vest Merkle airdrop claims and restart the schedule once it ended", async () => {
+    const leaves = [merkleLeaf(0, payer, 150), merkleLeaf(1, payer, 50)];
+    const proofs = [[Array.from(leaves[1])], [Array.from(leaves[0])]];
+    await createAirdrop(VESTING_AIRDROP_ID, 200, {
+      amountPerClaim: new anchor.BN(0),
+      mode: { merkle: { merkleRoot: Array.from(merkleNode(leaves[0], leaves[1])) } },
+      vesting: { cliffDuration: new anchor.BN(5), vestingDuration: new anchor.BN(15) },
+      eligibility: null,
+    });
+    const claimLeaf = (index: number, amount: number) => claimAirdrop(
+      VESTING_AIRDROP_ID,
+      signer,
+      { index: new anchor.BN(index), amount: new anchor.BN(amount), proof: proofs[index] },
+      null,
+      null,
+      true,
+    ).rpc({ skipPreflight: true });
+    const withdrawVested = () => program.methods
+      .withdrawVestedAirdrop(VESTING_AIRDROP_ID)
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+    const vestingPosition = airdropPDA("airdrop_vesting_position", VESTING_AIRDROP_ID, payer.toBuffer());
+    const sleep = (seconds: number) => new Promise(resolve => setTimeout(resolve, seconds*1000));
+
+    // Claimed tokens stay in the vault until they vest
+    const balanceBefore = await tokenBalance(payer);
+    await claimLeaf(0, 150);
+    assert.equal(await tokenBalance(payer), balanceBefore);
+    const position = await program.account.airdropVestingPosition.fetch(vestingPosition);
+    const total = BigInt(position.totalAmount.toString());
+    assert.equal(total, await scaled(BigInt(150)));
+    assert.equal(position.withdrawnAmount.toNumber(), 0);
+
+    await expectError(withdrawVested(), "NothingToWithdraw");
+
+    // Past the cliff part of the amount is released, linearly from the claim
+    await sleep(8);
+    await withdrawVested();
+    const partial = await tokenBalance(payer) - balanceBefore;
+    assert.equal(
+      partial.toString(),
+      (await program.account.airdropVestingPosition.fetch(vestingPosition)).withdrawnAmount.toString()
+    );
+    assert.isTrue(partial >= total * BigInt(5) / BigInt(15));
+    assert.isTrue(partial < total);
+
+    // Once the schedule ended another claim can't start a new one while tokens are still owed
+    await sleep(10);
+    await expectError(claimLeaf(1, 50), "AirdropVestingPositionEnded");
+
+    await withdrawVested();
+    assert.equal(await tokenBalance(payer) - balanceBefore, total);
+    await expectError(withdrawVested(), "NothingToWithdraw");
+
+    // Fully withdrawn, the next claim restarts the schedule with only its own amount
+    await claimLeaf(1, 50);
+    const restarted = await program.account.airdropVestingPosition.fetch(vestingPosition);
+    assert.equal(BigInt(restarted.totalAmount.toString()), await scaled(BigInt(50)));
+    assert.equal(restarted.withdrawnAmount.toNumber(), 0);
+    assert.isTrue(restarted.startDatetime.gt(position.startDatetime));
+    assert.equal(restarted.cliffDatetime.sub(restarted.startDatetime).toNumber(), 5);
+    assert.equal(restarted.endDatetime.sub(restarted.startDatetime).toNumber(), 15);
+    await expectError(withdrawVested(), "NothingToWithdraw");
+  })
+
+  it("Fund an airdrop vault from the treasury and close it into a tombstone", async () => {
+    const airdropVault = airdropPDA("airdrop_token_account", CLOSED_AIRDROP_ID);
+    const treasuryBefore = await vaultBalance(treasuryPDA);
+    await createAirdrop(CLOSED_AIRDROP_ID, 300, {
+      amountPerClaim: new anchor.BN(100),
+      mode: { open: {} },
+      vesting: null,
+      eligibility: null,
+    }, 5);
+    assert.equal(await vaultBalance(airdropVault), await scaled(BigInt(300)));
+    assert.equal(treasuryBefore - await vaultBalance(treasuryPDA), await scaled(BigInt(300)));
+
+    await claimAirdrop(CLOSED_AIRDROP_ID, signer, null, null).rpc({ skipPreflight: true });
+    assert.equal(await vaultBalance(airdropVault), await scaled(BigInt(200)));
+
+    const closeAirdrop = () => program.methods
+      .closeAirdrop(CLOSED_AIRDROP_ID)
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+    await expectError(closeAirdrop(), "AirdropNotEnded");
+
+    await new Promise(resolve => setTimeout(resolve, 7*1000));
+    const treasuryBeforeClose = await vaultBalance(treasuryPDA);
+    await closeAirdrop();
+
+    // Whatever wasn't claimed goes back to the treasury, the emptied vault and the airdrop are closed
+    assert.equal(await vaultBalance(treasuryPDA) - treasuryBeforeClose, await scaled(BigInt(200)));
+    assert.isNull(await connection.getAccountInfo(airdropVault));
+    assert.isNull(await connection.getAccountInfo(airdropPDA("airdrop", CLOSED_AIRDROP_ID)));
+    const tombstone = await program.account.airdropTombstone.fetch(airdropPDA("airdrop_tombstone", CLOSED_AIRDROP_ID));
+    assert.equal(tombstone.airdropId.toNumber(), CLOSED_AIRDROP_ID.toNumber());
+    assert.isAbove(tombstone.closedAt.toNumber(), 0);
+
+    // The tombstone keeps the id from being reused
+    await expectError(
+      createAirdrop(CLOSED_AIRDROP_ID, 300, {
+        amountPerClaim: new anchor.BN(100),
+        mode: { open: {} },
+        vesting: null,
+        eligibility: null,
+      }),
+      "AirdropIdAlreadyUsed"
+    );
+  })
+
+  it("Pause, extend, top up and cancel an airdrop", async () => {
+    const config = {
+      amountPerClaim: new anchor.BN(10),
+      mode: { open: {} },
+      vesting: null,
+      eligibility: null,
+    };
+    const airdropVault = airdropPDA("airdrop_token_account", MANAGED_AIRDROP_ID);
+    await createAirdrop(MANAGED_AIRDROP_ID, 100, config);
+    const claimer = await fundedWallet(0);
+
+    const setPaused = (isPaused: boolean) => program.methods
+      .setAirdropPaused(MANAGED_AIRDROP_ID, isPaused)
+      .accounts({
+        payer: payer,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+    await setPaused(true);
+    await expectError(claimAirdrop(MANAGED_AIRDROP_ID, claimer, null, null).rpc({ skipPreflight: true }), "AirdropPaused");
+    await setPaused(false);
+    await claimAirdrop(MANAGED_AIRDROP_ID, claimer, null, null).rpc({ skipPreflight: true });
+    assert.equal(await tokenBalance(claimer.publicKey), await scaled(BigInt(10)));
+
+    const extendAirdrop = (airdropId: anchor.BN, endDatetime: anchor.BN) => program.methods
+      .extendAirdrop(airdropId, endDatetime)
+      .accounts({
+        payer: payer,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+    const { endDatetime } = await program.account.airdrop.fetch(airdropPDA("airdrop", MANAGED_AIRDROP_ID));
+    await expectError(extendAirdrop(MANAGED_AIRDROP_ID, endDatetime.subn(1)), "InvalidAirdropStartEndDatetime");
+    await extendAirdrop(MANAGED_AIRDROP_ID, endDatetime.addn(60*60));
+    assert.isTrue((await program.account.airdrop.fetch(airdropPDA("airdrop", MANAGED_AIRDROP_ID))).endDatetime.eq(endDatetime.addn(60*60)));
+
+    // Claim records of an ended airdrop can be closed, reopening it would allow claiming twice
+    await createAirdrop(ENDED_AIRDROP_ID, 100, config, 2);
+    await new Promise(resolve => setTimeout(resolve, 4*1000));
+    const { endDatetime: endedDatetime } = await program.account.airdrop.fetch(airdropPDA("airdrop", ENDED_AIRDROP_ID));
+    await expectError(extendAirdrop(ENDED_AIRDROP_ID, endedDatetime.addn(60*60)), "AirdropEnded");
+
+    // Added supply moves from the treasury into the vault
+    const treasuryBefore = await vaultBalance(treasuryPDA);
+    const vaultBefore = await vaultBalance(airdropVault);
+    await program.methods
+      .increaseAirdropSupply(MANAGED_AIRDROP_ID, new anchor.BN(50))
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ skipPreflight: true });
+    assert.equal(treasuryBefore - await vaultBalance(treasuryPDA), await scaled(BigInt(50)));
+    assert.equal(await vaultBalance(airdropVault) - vaultBefore, await scaled(BigInt(50)));
+    assert.equal((await program.account.airdrop.fetch(airdropPDA("airdrop", MANAGED_AIRDROP_ID))).supply.toNumber(), 150);
+
+    // Cancelling returns supply - supplied to the treasury
+    const treasuryBeforeCancel = await vaultBalance(treasuryPDA);
+    const cancelTxSig = await program.methods
+      .cancelAirdrop(MANAGED_AIRDROP_ID)
+      .accounts({
+        payer: payer,
+        tokenMint: TOKEN_MINT,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc({ commitment: "confirmed" });
+    assert.equal(await vaultBalance(treasuryPDA) - treasuryBeforeCancel, await scaled(BigInt(140)));
+    const airdrop = await program.account.airdrop.fetch(airdropPDA("airdrop", MANAGED_AIRDROP_ID));
+    assert.isTrue(airdrop.isCancelled);
+    assert.equal(airdrop.supply.toNumber(), 10);
+
+    const cancelTx = await connection.getTransaction(cancelTxSig, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+    const events = [...new anchor.EventParser(program.programId, program.coder).parseLogs(cancelTx.meta.logMessages)];
+    const cancelled = events.find(event => event.name === "airdropCancelled");
+    assert.isDefined(cancelled);
+    assert.equal(cancelled.data.airdropId.toString(), MANAGED_AIRDROP_ID.toString());
+    assert.equal(cancelled.data.supplied.toString(), "10");
+    assert.equal(cancelled.data.unusedAmount.toString(), "140");
+
+    await expectError(claimAirdrop(MANAGED_AIRDROP_ID, signer, null, null).rpc({ skipPreflight: true }), "AirdropCancelled");
+    await expectError(setPaused(false), "AirdropCancelled");
+  })
+});